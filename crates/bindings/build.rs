@@ -24,6 +24,8 @@ fn main() -> Result<()> {
     )?)?;
 
     webview2_bindgen::update_bindings()?;
+    webview2_enums::generate_enums()?;
+    webview2_enums::generate_flags()?;
 
     Ok(())
 }
@@ -404,7 +406,7 @@ mod webview2_bindgen {
         )?;
         let replacement = r#"
             #[cfg_attr(target_env = "msvc", link(name = "WebView2LoaderStatic", kind = "static"))]
-            #[cfg_attr(not(target_env = "msvc"), link(name = "WebView2Loader"))]
+            #[cfg_attr(all(windows, not(target_env = "msvc")), link(name = "WebView2Loader"))]
         "#;
         let pattern2 = Regex::new(r#"#\s*!\s*\[[^\]]*\]"#)?;
         let bindings = pattern.replace_all(&bindings, replacement);
@@ -426,3 +428,258 @@ mod webview2_bindgen {
         Ok(updated)
     }
 }
+
+mod webview2_enums {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt::Write as _,
+        fs,
+    };
+
+    use regex::Regex;
+
+    use super::webview2_path::*;
+
+    /// Name of the catch-all variant for values which are not in the bindings.
+    const UNKNOWN: &str = "Unknown";
+
+    /// Generate a Rust `enum` for each `COREWEBVIEW2_*` newtype in the bindings in `OUT_DIR`, and
+    /// write them to `OUT_DIR/enums.rs` for [enums.rs](./src/enums.rs) to include. The flags
+    /// types, which implement `BitOr`, are skipped because their values can be combined.
+    pub fn generate_enums() -> super::Result<()> {
+        let bindings = read_generated_bindings()?;
+
+        let mut dest_path = get_out_dir()?;
+        dest_path.push("enums.rs");
+        fs::write(dest_path, write_enums(&parse_enums(&bindings)?))?;
+        Ok(())
+    }
+
+    /// Generate a `webview2_flags!` invocation for each flags type in the bindings in `OUT_DIR`,
+    /// and write them to `OUT_DIR/flags.rs` for [flags.rs](./src/flags.rs) to include.
+    pub fn generate_flags() -> super::Result<()> {
+        let bindings = read_generated_bindings()?;
+
+        let mut dest_path = get_out_dir()?;
+        dest_path.push("flags.rs");
+        fs::write(dest_path, write_flags(&parse_flags(&bindings)?))?;
+        Ok(())
+    }
+
+    fn read_generated_bindings() -> super::Result<String> {
+        let mut source_path = get_out_dir()?;
+        source_path.push("mod.rs");
+        Ok(fs::read_to_string(source_path)?)
+    }
+
+    /// Map each enum type to its constants, in declaration order, as `(name, value)` pairs.
+    fn parse_enums(bindings: &str) -> super::Result<BTreeMap<String, Vec<(String, i32)>>> {
+        let type_pattern =
+            Regex::new(r#"pub\s+struct\s+(COREWEBVIEW2_\w+)\s*\(\s*pub\s+i32\s*\)"#)?;
+        let flags_pattern = Regex::new(r#"BitOr\s+for\s+(COREWEBVIEW2_\w+)"#)?;
+        let const_pattern = Regex::new(
+            r#"pub\s+const\s+(COREWEBVIEW2_\w+)\s*:\s*(COREWEBVIEW2_\w+)\s*=\s*COREWEBVIEW2_\w+\s*\(\s*(-?\d+)i32\s*\)"#,
+        )?;
+
+        let flags: BTreeSet<_> = flags_pattern
+            .captures_iter(bindings)
+            .map(|captures| captures[1].to_string())
+            .collect();
+        let mut enums: BTreeMap<_, _> = type_pattern
+            .captures_iter(bindings)
+            .map(|captures| captures[1].to_string())
+            .filter(|name| !flags.contains(name))
+            .map(|name| (name, Vec::new()))
+            .collect();
+
+        for captures in const_pattern.captures_iter(bindings) {
+            if let Some(constants) = enums.get_mut(&captures[2]) {
+                let value = captures[3].parse().expect("value is an i32 literal");
+                constants.push((captures[1].to_string(), value));
+            }
+        }
+
+        enums.retain(|_, constants| !constants.is_empty());
+        if enums.is_empty() {
+            Err(super::Error::MissingTypedef)
+        } else {
+            Ok(enums)
+        }
+    }
+
+    /// Map each flags type, i.e. each newtype which implements `BitOr`, to the names of its
+    /// constants, in declaration order.
+    fn parse_flags(bindings: &str) -> super::Result<BTreeMap<String, Vec<String>>> {
+        let flags_pattern = Regex::new(r#"BitOr\s+for\s+(COREWEBVIEW2_\w+)"#)?;
+        let const_pattern = Regex::new(
+            r#"pub\s+const\s+(COREWEBVIEW2_\w+)\s*:\s*(COREWEBVIEW2_\w+)\s*=\s*COREWEBVIEW2_\w+\s*\(\s*\d+u32\s*\)"#,
+        )?;
+
+        let mut flags: BTreeMap<_, _> = flags_pattern
+            .captures_iter(bindings)
+            .map(|captures| (captures[1].to_string(), Vec::new()))
+            .collect();
+
+        for captures in const_pattern.captures_iter(bindings) {
+            if let Some(constants) = flags.get_mut(&captures[2]) {
+                constants.push(captures[1].to_string());
+            }
+        }
+
+        flags.retain(|_, constants| !constants.is_empty());
+        if flags.is_empty() {
+            Err(super::Error::MissingTypedef)
+        } else {
+            Ok(flags)
+        }
+    }
+
+    /// Convert `SCREAMING_SNAKE_CASE` to `CamelCase`.
+    fn camel_case(name: &str) -> String {
+        name.split('_')
+            .filter(|word| !word.is_empty())
+            .map(|word| {
+                let mut chars = word.chars();
+                let first = chars.next().expect("word is not empty");
+                first.to_string() + &chars.as_str().to_ascii_lowercase()
+            })
+            .collect()
+    }
+
+    fn write_enums(enums: &BTreeMap<String, Vec<(String, i32)>>) -> String {
+        let mut source = String::new();
+
+        for (type_name, constants) in enums {
+            let enum_name = camel_case(type_name.trim_start_matches("COREWEBVIEW2_"));
+            let last_word = camel_case(type_name.rsplit('_').next().unwrap_or_default());
+            let variants: Vec<_> = constants
+                .iter()
+                .map(|(name, value)| {
+                    let variant = camel_case(&name[type_name.len()..]);
+                    // e.g. `COREWEBVIEW2_WEB_ERROR_STATUS_UNKNOWN` would collide with the fallback.
+                    let variant = if variant == UNKNOWN {
+                        format!("{}{}", variant, last_word)
+                    } else {
+                        variant
+                    };
+                    (name.as_str(), variant, *value)
+                })
+                .collect();
+
+            write_enum(&mut source, type_name, &enum_name, &variants).expect("write to String");
+        }
+
+        source
+    }
+
+    fn write_flags(flags: &BTreeMap<String, Vec<String>>) -> String {
+        let mut source = String::new();
+
+        for (type_name, constants) in flags {
+            let flags_name = camel_case(type_name.trim_start_matches("COREWEBVIEW2_"));
+            writeln!(source, "webview2_flags!({flags_name}: {type_name} {{")
+                .expect("write to String");
+            for name in constants {
+                let flag = &name[type_name.len() + 1..];
+                writeln!(source, "    {flag} = {name},").expect("write to String");
+            }
+            writeln!(source, "}});\n").expect("write to String");
+        }
+
+        source
+    }
+
+    fn write_enum(
+        source: &mut String,
+        type_name: &str,
+        enum_name: &str,
+        variants: &[(&str, String, i32)],
+    ) -> std::fmt::Result {
+        writeln!(
+            source,
+            r#"/// Rust enum for [`{type_name}`]. Values which are not in the bindings, e.g. ones which were
+/// added in a newer WebView2 Runtime, are kept in [`{enum_name}::{UNKNOWN}`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum {enum_name} {{"#
+        )?;
+        for (_, variant, _) in variants {
+            writeln!(source, "    {variant},")?;
+        }
+        writeln!(
+            source,
+            r#"    {UNKNOWN}(i32),
+}}
+
+impl {enum_name} {{
+    /// Name of the matching constant in the bindings, or `None` for [`{enum_name}::{UNKNOWN}`].
+    pub fn name(&self) -> Option<&'static str> {{
+        match self {{"#
+        )?;
+        for (name, variant, _) in variants {
+            writeln!(source, r#"            Self::{variant} => Some("{name}"),"#)?;
+        }
+        writeln!(
+            source,
+            r#"            Self::{UNKNOWN}(_) => None,
+        }}
+    }}
+}}
+
+impl From<{type_name}> for {enum_name} {{
+    fn from(value: {type_name}) -> Self {{
+        match value.0 {{"#
+        )?;
+        for (_, variant, value) in variants {
+            writeln!(source, "            {value} => Self::{variant},")?;
+        }
+        writeln!(
+            source,
+            r#"            value => Self::{UNKNOWN}(value),
+        }}
+    }}
+}}
+
+impl From<{enum_name}> for {type_name} {{
+    fn from(value: {enum_name}) -> Self {{
+        match value {{"#
+        )?;
+        for (name, variant, _) in variants {
+            writeln!(source, "            {enum_name}::{variant} => {name},")?;
+        }
+        writeln!(
+            source,
+            r#"            {enum_name}::{UNKNOWN}(value) => {type_name}(value),
+        }}
+    }}
+}}
+
+impl TryFrom<i32> for {enum_name} {{
+    type Error = UnknownEnumValue;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {{
+        match Self::from({type_name}(value)) {{
+            Self::{UNKNOWN}(value) => Err(UnknownEnumValue(value)),
+            known => Ok(known),
+        }}
+    }}
+}}
+
+impl From<{enum_name}> for i32 {{
+    fn from(value: {enum_name}) -> Self {{
+        {type_name}::from(value).0
+    }}
+}}
+
+impl fmt::Display for {enum_name} {{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {{
+        match self {{
+            Self::{UNKNOWN}(value) => write!(f, "{type_name}({{}})", value),
+            known => f.write_str(known.name().unwrap_or_default()),
+        }}
+    }}
+}}
+"#
+        )
+    }
+}
@@ -1335,7 +1335,7 @@ where
         target_env = "msvc",
         link(name = "WebView2LoaderStatic", kind = "static")
     )]
-    #[cfg_attr(not(target_env = "msvc"), link(name = "WebView2Loader"))]
+    #[cfg_attr(all(windows, not(target_env = "msvc")), link(name = "WebView2Loader"))]
     extern "system" {
         fn CompareBrowserVersions(
             version1: ::windows::core::PCWSTR,
@@ -1363,7 +1363,7 @@ where
         target_env = "msvc",
         link(name = "WebView2LoaderStatic", kind = "static")
     )]
-    #[cfg_attr(not(target_env = "msvc"), link(name = "WebView2Loader"))]
+    #[cfg_attr(all(windows, not(target_env = "msvc")), link(name = "WebView2Loader"))]
     extern "system" {
         fn CreateCoreWebView2Environment(
             environmentcreatedhandler: *mut ::core::ffi::c_void,
@@ -1390,7 +1390,7 @@ where
         target_env = "msvc",
         link(name = "WebView2LoaderStatic", kind = "static")
     )]
-    #[cfg_attr(not(target_env = "msvc"), link(name = "WebView2Loader"))]
+    #[cfg_attr(all(windows, not(target_env = "msvc")), link(name = "WebView2Loader"))]
     extern "system" {
         fn CreateCoreWebView2EnvironmentWithOptions(
             browserexecutablefolder: ::windows::core::PCWSTR,
@@ -1419,7 +1419,7 @@ where
         target_env = "msvc",
         link(name = "WebView2LoaderStatic", kind = "static")
     )]
-    #[cfg_attr(not(target_env = "msvc"), link(name = "WebView2Loader"))]
+    #[cfg_attr(all(windows, not(target_env = "msvc")), link(name = "WebView2Loader"))]
     extern "system" {
         fn GetAvailableCoreWebView2BrowserVersionString(
             browserexecutablefolder: ::windows::core::PCWSTR,
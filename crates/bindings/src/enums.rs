@@ -0,0 +1,72 @@
+use std::fmt;
+
+use crate::Microsoft::Web::WebView2::Win32::*;
+
+/// Error returned by the `TryFrom<i32>` conversions when the value does not match any of the
+/// constants in the bindings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnknownEnumValue(pub i32);
+
+impl fmt::Display for UnknownEnumValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown enum value: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownEnumValue {}
+
+include!(concat!(env!("OUT_DIR"), "/enums.rs"));
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn convert_known() {
+        let status = WebErrorStatus::from(COREWEBVIEW2_WEB_ERROR_STATUS_CONNECTION_ABORTED);
+        assert_eq!(status, WebErrorStatus::ConnectionAborted);
+        assert_eq!(
+            COREWEBVIEW2_WEB_ERROR_STATUS::from(status),
+            COREWEBVIEW2_WEB_ERROR_STATUS_CONNECTION_ABORTED
+        );
+        assert_eq!(
+            PermissionKind::try_from(COREWEBVIEW2_PERMISSION_KIND_CAMERA.0),
+            Ok(PermissionKind::Camera)
+        );
+        assert_eq!(
+            i32::from(ProcessFailedKind::RenderProcessUnresponsive),
+            COREWEBVIEW2_PROCESS_FAILED_KIND_RENDER_PROCESS_UNRESPONSIVE.0
+        );
+        assert_eq!(
+            WebErrorStatus::from(COREWEBVIEW2_WEB_ERROR_STATUS_UNKNOWN),
+            WebErrorStatus::UnknownStatus
+        );
+    }
+
+    #[test]
+    fn convert_unknown() {
+        let reason = DownloadInterruptReason::from(COREWEBVIEW2_DOWNLOAD_INTERRUPT_REASON(9999));
+        assert_eq!(reason, DownloadInterruptReason::Unknown(9999));
+        assert_eq!(
+            COREWEBVIEW2_DOWNLOAD_INTERRUPT_REASON::from(reason),
+            COREWEBVIEW2_DOWNLOAD_INTERRUPT_REASON(9999)
+        );
+        assert_eq!(
+            DownloadInterruptReason::try_from(9999),
+            Err(UnknownEnumValue(9999))
+        );
+    }
+
+    #[test]
+    fn display_names() {
+        assert_eq!(
+            PermissionKind::Microphone.to_string(),
+            "COREWEBVIEW2_PERMISSION_KIND_MICROPHONE"
+        );
+        assert_eq!(
+            PermissionKind::Unknown(42).to_string(),
+            "COREWEBVIEW2_PERMISSION_KIND(42)"
+        );
+        assert_eq!(PermissionKind::Unknown(42).name(), None);
+    }
+}
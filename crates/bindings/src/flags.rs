@@ -0,0 +1,346 @@
+use std::{
+    cmp::Reverse,
+    fmt,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Sub, SubAssign},
+};
+
+use crate::Microsoft::Web::WebView2::Win32::*;
+
+/// Declare a bit set type for one of the `COREWEBVIEW2_*` flags newtypes, with an associated
+/// constant for each named flag. The conversions to and from the newtype keep every bit, even
+/// the ones which do not have a name in the bindings, so values from a newer WebView2 Runtime
+/// round-trip without loss.
+macro_rules! webview2_flags {
+    ($name:ident: $raw:ident { $($flag:ident = $value:ident,)* }) => {
+        #[doc = concat!("Bit set for [`", stringify!($raw), "`].")]
+        #[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        #[cfg_attr(feature = "serde", serde(transparent))]
+        pub struct $name(u32);
+
+        impl $name {
+            $(
+                #[doc = concat!("[`", stringify!($value), "`]")]
+                pub const $flag: Self = Self($value.0);
+            )*
+
+            /// Every named flag, in declaration order.
+            const FLAGS: &'static [(&'static str, Self)] = &[$((stringify!($flag), Self::$flag),)*];
+
+            pub const fn empty() -> Self {
+                Self(0)
+            }
+
+            /// Every flag which has a name in the bindings.
+            pub const fn all() -> Self {
+                Self(0 $(| $value.0)*)
+            }
+
+            pub const fn bits(&self) -> u32 {
+                self.0
+            }
+
+            /// Convert from bits, or return `None` if any of them do not have a name.
+            pub const fn from_bits(bits: u32) -> Option<Self> {
+                if bits & !Self::all().0 == 0 {
+                    Some(Self(bits))
+                } else {
+                    None
+                }
+            }
+
+            /// Convert from bits, and drop any of them which do not have a name.
+            pub const fn from_bits_truncate(bits: u32) -> Self {
+                Self(bits & Self::all().0)
+            }
+
+            /// Convert from bits, and keep any of them which do not have a name.
+            pub const fn from_bits_retain(bits: u32) -> Self {
+                Self(bits)
+            }
+
+            pub const fn is_empty(&self) -> bool {
+                self.0 == 0
+            }
+
+            pub const fn is_all(&self) -> bool {
+                self.0 & Self::all().0 == Self::all().0
+            }
+
+            /// Check if every flag in `other` is also set in `self`.
+            pub const fn contains(&self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Check if any flag in `other` is also set in `self`.
+            pub const fn intersects(&self, other: Self) -> bool {
+                self.0 & other.0 != 0
+            }
+
+            pub fn insert(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+
+            pub fn remove(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+
+            pub fn toggle(&mut self, other: Self) {
+                self.0 ^= other.0;
+            }
+
+            /// Insert or remove `other` depending on `value`.
+            pub fn set(&mut self, other: Self, value: bool) {
+                if value {
+                    self.insert(other);
+                } else {
+                    self.remove(other);
+                }
+            }
+
+            /// Iterate over the named flags which are set, followed by a single value with any
+            /// remaining bits which do not have a name.
+            pub fn iter(&self) -> impl Iterator<Item = Self> {
+                let mut flags: Vec<_> = self.iter_names().map(|(_, flag)| flag).collect();
+                let remaining = self.0 & !Self::all().0;
+                if remaining != 0 {
+                    flags.push(Self(remaining));
+                }
+                flags.into_iter()
+            }
+
+            /// Iterate over the names and values of the named flags which are set. Composite flags,
+            /// e.g. `ALL_PROFILE`, come first, and a flag is skipped if all of its bits were
+            /// already yielded, so each bit is only named once.
+            pub fn iter_names(&self) -> impl Iterator<Item = (&'static str, Self)> {
+                let mut flags: Vec<_> = Self::FLAGS
+                    .iter()
+                    .copied()
+                    .filter(|(_, flag)| !flag.is_empty() && self.contains(*flag))
+                    .collect();
+                flags.sort_by_key(|(_, flag)| Reverse(flag.0.count_ones()));
+
+                let mut remaining = self.0;
+                flags.into_iter().filter(move |(_, flag)| {
+                    let yielded = remaining & flag.0 != 0;
+                    remaining &= !flag.0;
+                    yielded
+                })
+            }
+        }
+
+        impl From<$raw> for $name {
+            fn from(value: $raw) -> Self {
+                Self(value.0)
+            }
+        }
+
+        impl From<$name> for $raw {
+            fn from(value: $name) -> Self {
+                $raw(value.0)
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}(", stringify!($name))?;
+                let mut empty = true;
+                for (name, _) in self.iter_names() {
+                    if !empty {
+                        f.write_str(" | ")?;
+                    }
+                    f.write_str(name)?;
+                    empty = false;
+                }
+                let remaining = self.0 & !Self::all().0;
+                if remaining != 0 || empty {
+                    if !empty {
+                        f.write_str(" | ")?;
+                    }
+                    write!(f, "{:#x}", remaining)?;
+                }
+                f.write_str(")")
+            }
+        }
+
+        impl BitOr for $name {
+            type Output = Self;
+
+            fn bitor(self, other: Self) -> Self {
+                Self(self.0 | other.0)
+            }
+        }
+
+        impl BitOrAssign for $name {
+            fn bitor_assign(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+        }
+
+        impl BitAnd for $name {
+            type Output = Self;
+
+            fn bitand(self, other: Self) -> Self {
+                Self(self.0 & other.0)
+            }
+        }
+
+        impl BitAndAssign for $name {
+            fn bitand_assign(&mut self, other: Self) {
+                self.0 &= other.0;
+            }
+        }
+
+        impl BitXor for $name {
+            type Output = Self;
+
+            fn bitxor(self, other: Self) -> Self {
+                Self(self.0 ^ other.0)
+            }
+        }
+
+        impl BitXorAssign for $name {
+            fn bitxor_assign(&mut self, other: Self) {
+                self.0 ^= other.0;
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                Self(self.0 & !other.0)
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+        }
+
+        impl Not for $name {
+            type Output = Self;
+
+            /// Complement within the named flags, like [`Self::from_bits_truncate`].
+            fn not(self) -> Self {
+                Self::from_bits_truncate(!self.0)
+            }
+        }
+
+        impl FromIterator<$name> for $name {
+            fn from_iter<T: IntoIterator<Item = Self>>(iter: T) -> Self {
+                iter.into_iter().fold(Self::empty(), BitOr::bitor)
+            }
+        }
+
+        impl Extend<$name> for $name {
+            fn extend<T: IntoIterator<Item = Self>>(&mut self, iter: T) {
+                for flag in iter {
+                    self.insert(flag);
+                }
+            }
+        }
+    };
+}
+
+include!(concat!(env!("OUT_DIR"), "/flags.rs"));
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn combine_flags() {
+        let mut kinds = BrowsingDataKinds::COOKIES | BrowsingDataKinds::DISK_CACHE;
+        assert!(kinds.contains(BrowsingDataKinds::COOKIES));
+        assert!(!kinds.contains(BrowsingDataKinds::COOKIES | BrowsingDataKinds::SETTINGS));
+        assert!(kinds.intersects(BrowsingDataKinds::COOKIES | BrowsingDataKinds::SETTINGS));
+
+        kinds -= BrowsingDataKinds::COOKIES;
+        assert_eq!(kinds, BrowsingDataKinds::DISK_CACHE);
+        kinds.set(BrowsingDataKinds::SETTINGS, true);
+        kinds.toggle(BrowsingDataKinds::DISK_CACHE);
+        assert_eq!(kinds, BrowsingDataKinds::SETTINGS);
+
+        assert!((!PdfToolbarItems::empty()).is_all());
+        assert!(PdfToolbarItems::NONE.is_empty());
+        assert_eq!(!PdfToolbarItems::all(), PdfToolbarItems::empty());
+    }
+
+    #[test]
+    fn iterate_flags() {
+        let keys = MouseEventVirtualKeys::SHIFT | MouseEventVirtualKeys::LEFT_BUTTON;
+        assert_eq!(
+            keys.iter_names().map(|(name, _)| name).collect::<Vec<_>>(),
+            vec!["LEFT_BUTTON", "SHIFT"]
+        );
+        assert_eq!(keys.iter().collect::<MouseEventVirtualKeys>(), keys);
+
+        let unnamed = MouseEventVirtualKeys::from_bits_retain(0x1000) | keys;
+        assert_eq!(
+            unnamed.iter().last(),
+            Some(MouseEventVirtualKeys::from_bits_retain(0x1000))
+        );
+    }
+
+    #[test]
+    fn composite_flags() {
+        let all = BrowsingDataKinds::ALL_PROFILE;
+        assert_eq!(
+            all.iter_names().map(|(name, _)| name).collect::<Vec<_>>(),
+            vec!["ALL_PROFILE"]
+        );
+        assert_eq!(all.iter().collect::<Vec<_>>(), vec![all]);
+        assert_eq!(format!("{:?}", all), "BrowsingDataKinds(ALL_PROFILE)");
+
+        let kinds = BrowsingDataKinds::ALL_SITE | BrowsingDataKinds::DISK_CACHE;
+        assert_eq!(
+            kinds.iter_names().map(|(name, _)| name).collect::<Vec<_>>(),
+            vec!["ALL_SITE", "DISK_CACHE"]
+        );
+        assert_eq!(
+            kinds
+                .iter()
+                .map(|flag| flag.bits().count_ones())
+                .sum::<u32>(),
+            kinds.bits().count_ones()
+        );
+        assert_eq!(
+            format!("{:?}", kinds),
+            "BrowsingDataKinds(ALL_SITE | DISK_CACHE)"
+        );
+    }
+
+    #[test]
+    fn debug_names() {
+        assert_eq!(
+            format!(
+                "{:?}",
+                PdfToolbarItems::SAVE
+                    | PdfToolbarItems::SEARCH
+                    | PdfToolbarItems::from_bits_retain(0x10000)
+            ),
+            "PdfToolbarItems(SAVE | SEARCH | 0x10000)"
+        );
+        assert_eq!(
+            format!("{:?}", PdfToolbarItems::NONE),
+            "PdfToolbarItems(0x0)"
+        );
+    }
+
+    #[test]
+    fn lossless_conversions() {
+        let raw = COREWEBVIEW2_BROWSING_DATA_KINDS(
+            COREWEBVIEW2_BROWSING_DATA_KINDS_COOKIES.0 | 0x8000_0000,
+        );
+        let kinds = BrowsingDataKinds::from(raw);
+        assert_eq!(kinds.bits(), raw.0);
+        assert_eq!(COREWEBVIEW2_BROWSING_DATA_KINDS::from(kinds), raw);
+        assert_eq!(BrowsingDataKinds::from_bits(raw.0), None);
+        assert_eq!(
+            BrowsingDataKinds::from_bits_truncate(raw.0),
+            BrowsingDataKinds::COOKIES
+        );
+    }
+}
@@ -16,6 +16,8 @@ pub mod Microsoft {
 }
 
 pub mod callback_interfaces;
+pub mod enums;
+pub mod flags;
 
 #[cfg(test)]
 mod test {
@@ -1,15 +1,17 @@
 use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::{format_ident, quote};
 use syn::{
     parenthesized,
     parse::{Parse, ParseStream},
     parse_macro_input,
     punctuated::Punctuated,
-    Ident, Result, Token, TypePath, Visibility,
+    Error, Generics, Ident, Result, Token, TypePath, Visibility,
 };
 
 struct CallbackTypes {
-    pub interface: TypePath,
+    pub paren: Span,
+    pub interface: Ident,
     pub arg_1: TypePath,
     pub arg_2: Option<TypePath>,
 }
@@ -17,22 +19,72 @@ struct CallbackTypes {
 impl Parse for CallbackTypes {
     fn parse(input: ParseStream) -> Result<Self> {
         let content;
-        parenthesized!(content in input);
+        let paren = parenthesized!(content in input);
         let args: Punctuated<TypePath, Token![,]> = content.parse_terminated(TypePath::parse)?;
         input.parse::<Token![;]>()?;
+
+        if let Some(extra) = args.iter().nth(3) {
+            return Err(Error::new_spanned(
+                extra,
+                "too many fields, expected (interface, arg_1) or (interface, arg_1, arg_2)",
+            ));
+        }
+
         let mut args = args.into_iter();
         if let (Some(interface), Some(arg_1), arg_2) = (args.next(), args.next(), args.next()) {
             Ok(CallbackTypes {
-                interface,
+                paren: paren.span,
+                interface: parse_interface(&interface)?,
                 arg_1,
                 arg_2,
             })
         } else {
-            Err(content.error("expected (interface, arg_1, arg_2)"))
+            Err(Error::new(
+                paren.span,
+                "too few fields, expected (interface, arg_1) or (interface, arg_1, arg_2)",
+            ))
         }
     }
 }
 
+/// Get the name of the COM interface from the first field, which should look like
+/// `ICoreWebView2...Handler` with an optional module path in front of it.
+fn parse_interface(interface: &TypePath) -> Result<Ident> {
+    let segment = match (&interface.qself, interface.path.segments.last()) {
+        (None, Some(segment)) if segment.arguments.is_empty() => segment,
+        _ => {
+            return Err(Error::new_spanned(
+                interface,
+                "the first field should be a COM interface without any generic arguments",
+            ))
+        }
+    };
+
+    let name = segment.ident.to_string();
+    let mut chars = name.chars();
+    if chars.next() != Some('I') || !matches!(chars.next(), Some(c) if c.is_uppercase()) {
+        return Err(Error::new_spanned(
+            &segment.ident,
+            format!(
+                "the first field should be a COM interface, but `{}` does not start with `I`",
+                name
+            ),
+        ));
+    }
+
+    if !name.ends_with("Handler") {
+        return Err(Error::new_spanned(
+            &segment.ident,
+            format!(
+                "the first field should be a callback interface, but `{}` does not end in `Handler`",
+                name
+            ),
+        ));
+    }
+
+    Ok(segment.ident.clone())
+}
+
 struct CallbackStruct {
     pub vis: Visibility,
     _struct_token: Token![struct],
@@ -42,39 +94,139 @@ struct CallbackStruct {
 
 impl Parse for CallbackStruct {
     fn parse(input: ParseStream) -> Result<Self> {
+        let vis = input.parse()?;
+        let struct_token = input.parse()?;
+        let ident = input.parse()?;
+
+        let generics: Generics = input.parse()?;
+        if !generics.params.is_empty() {
+            return Err(Error::new_spanned(
+                generics,
+                "callback structs cannot have generic parameters",
+            ));
+        }
+
         Ok(CallbackStruct {
-            vis: input.parse()?,
-            _struct_token: input.parse()?,
-            ident: input.parse()?,
+            vis,
+            _struct_token: struct_token,
+            ident,
             args: input.parse()?,
         })
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CallbackKind {
+    Completed,
+    Event,
+}
+
+impl CallbackKind {
+    fn from_interface(interface: &Ident) -> Option<Self> {
+        let name = interface.to_string();
+        if name.ends_with("CompletedHandler") {
+            Some(CallbackKind::Completed)
+        } else if name.ends_with("EventHandler") {
+            Some(CallbackKind::Event)
+        } else {
+            None
+        }
+    }
+
+    fn attr_name(self) -> &'static str {
+        match self {
+            CallbackKind::Completed => "completed",
+            CallbackKind::Event => "event",
+        }
+    }
+}
+
+struct CallbackAttr {
+    pub kind: Option<(Ident, CallbackKind)>,
+}
+
+impl Parse for CallbackAttr {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.is_empty() {
+            return Ok(CallbackAttr { kind: None });
+        }
+
+        let ident: Ident = input.parse()?;
+        let kind = match ident.to_string().as_str() {
+            "completed" => CallbackKind::Completed,
+            "event" => CallbackKind::Event,
+            _ => return Err(Error::new(ident.span(), "expected `completed` or `event`")),
+        };
+
+        Ok(CallbackAttr {
+            kind: Some((ident, kind)),
+        })
+    }
+}
+
+/// Implement a callback handler using the types specified as tuple struct fields. Interfaces
+/// whose names end in `CompletedHandler` get the same implementation as `#[completed_callback]`,
+/// and interfaces whose names end in `EventHandler` get the same implementation as
+/// `#[event_callback]`. The choice can be spelled out with `#[callback(completed)]` or
+/// `#[callback(event)]`, but it must agree with the interface name.
+#[proc_macro_attribute]
+pub fn callback(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let attr = parse_macro_input!(attr as CallbackAttr);
+    let ast = parse_macro_input!(input as CallbackStruct);
+
+    let gen = match get_callback_kind(&attr, &ast) {
+        Ok(CallbackKind::Completed) => impl_completed_callback(&ast),
+        Ok(CallbackKind::Event) => impl_event_callback(&ast),
+        Err(error) => Err(error),
+    };
+
+    gen.unwrap_or_else(|error| error.to_compile_error()).into()
+}
+
+fn get_callback_kind(attr: &CallbackAttr, ast: &CallbackStruct) -> Result<CallbackKind> {
+    let interface = &ast.args.interface;
+    let inferred = CallbackKind::from_interface(interface);
+
+    match (&attr.kind, inferred) {
+        (Some((ident, explicit)), Some(inferred)) if *explicit != inferred => Err(Error::new(
+            ident.span(),
+            format!(
+                "`{}` contradicts the interface name, which implies `#[callback({})]`",
+                explicit.attr_name(),
+                inferred.attr_name()
+            ),
+        )),
+        (Some((_, explicit)), _) => Ok(*explicit),
+        (None, Some(inferred)) => Ok(inferred),
+        (None, None) => Err(Error::new_spanned(
+            interface,
+            "interface name should end in `CompletedHandler` or `EventHandler`, \
+             or use `#[callback(completed)]` or `#[callback(event)]`",
+        )),
+    }
+}
+
 /// Implement a `CompletedCallback` using the types specified as tuple struct fields.
 #[proc_macro_attribute]
 pub fn completed_callback(_attr: TokenStream, input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as CallbackStruct);
     impl_completed_callback(&ast)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
 }
 
-fn impl_completed_callback(ast: &CallbackStruct) -> TokenStream {
+fn impl_completed_callback(ast: &CallbackStruct) -> Result<TokenStream2> {
     let vis = &ast.vis;
 
     let name = &ast.ident;
     let closure = get_closure(name);
     let interface = &ast.args.interface;
-    let interface = interface
-        .path
-        .segments
-        .last()
-        .expect("completed_callback should always specify an interface");
-    let interface_impl = format_ident!("{}_Impl", interface.ident);
+    let interface_impl = format_ident!("{}_Impl", interface);
 
     let arg_1 = &ast.args.arg_1;
     let arg_2 = &ast.args.arg_2;
 
-    let msg = format!("Implementation of [`{}`].", interface.ident);
+    let msg = format!("Implementation of [`{}`].", interface);
 
     let gen = match arg_2 {
         Some(arg_2) => quote! {
@@ -82,7 +234,7 @@ fn impl_completed_callback(ast: &CallbackStruct) -> TokenStream {
 
             #[doc = #msg]
             #[implement(#interface)]
-            #vis struct #name(::std::cell::UnsafeCell<Option<#closure>>);
+            #vis struct #name(::std::cell::Cell<Option<#closure>>);
 
             impl #name {
                 pub fn create(
@@ -96,18 +248,55 @@ fn impl_completed_callback(ast: &CallbackStruct) -> TokenStream {
                         dyn FnOnce(#interface) -> crate::Result<()>,
                     >,
                     completed: #closure,
+                ) -> crate::Result<()> {
+                    Self::wait_for_async_operation_until(closure, completed, None)
+                }
+
+                pub fn wait_for_async_operation_until(
+                    closure: Box<
+                        dyn FnOnce(#interface) -> crate::Result<()>,
+                    >,
+                    completed: #closure,
+                    deadline: Option<::std::time::Instant>,
                 ) -> crate::Result<()> {
                     let (tx, rx) = ::std::sync::mpsc::channel();
                     let completed: #closure =
                         Box::new(move |arg_1, arg_2| -> ::windows::core::Result<()> {
-                            let result = completed(arg_1, arg_2).map_err(crate::Error::WindowsError);
-                            tx.send(result).expect("send over mpsc channel");
+                            let result = completed(arg_1, arg_2).map_err(crate::Error::from);
+                            // The receiver is gone if the wait already timed out or the message
+                            // loop quit, and then nobody is waiting for the result.
+                            let _ = tx.send(result);
                             Ok(())
                         });
                     let callback = Self::create(completed);
 
                     closure(callback)?;
-                    crate::wait_with_pump(rx)?
+                    crate::wait_with_pump_until(rx, deadline, None)?
+                }
+
+                pub fn future(
+                    closure: Box<
+                        dyn FnOnce(#interface) -> crate::Result<()>,
+                    >,
+                ) -> impl ::std::future::Future<
+                    Output = crate::Result<(
+                        <#arg_1 as ClosureArg>::Output,
+                        <#arg_2 as ClosureArg>::Output,
+                    )>,
+                > {
+                    let (tx, rx) = ::futures::channel::oneshot::channel();
+                    let completed: #closure =
+                        Box::new(move |arg_1, arg_2| -> ::windows::core::Result<()> {
+                            let _ = tx.send((arg_1, arg_2));
+                            Ok(())
+                        });
+                    let callback = Self::create(completed);
+                    let started = closure(callback);
+
+                    async move {
+                        started?;
+                        rx.await.map_err(|_| crate::Error::TaskCanceled)
+                    }
                 }
             }
 
@@ -118,13 +307,15 @@ fn impl_completed_callback(ast: &CallbackStruct) -> TokenStream {
                     arg_1: <#arg_1 as InvokeArg<'a>>::Input,
                     arg_2: <#arg_2 as InvokeArg<'a>>::Input,
                 ) -> ::windows::core::Result<()> {
-                    match unsafe { (*self.0.get()).take() } {
-                        Some(completed) => completed(
-                            <#arg_1 as InvokeArg<'a>>::convert(arg_1),
-                            <#arg_2 as InvokeArg<'a>>::convert(arg_2),
-                        ),
-                        None => Ok(()),
-                    }
+                    crate::catch_invoke(move || {
+                        match self.0.take() {
+                            Some(completed) => completed(
+                                <#arg_1 as InvokeArg<'a>>::convert(arg_1),
+                                <#arg_2 as InvokeArg<'a>>::convert(arg_2),
+                            ),
+                            None => Ok(()),
+                        }
+                    })
                 }
             }
         },
@@ -133,7 +324,7 @@ fn impl_completed_callback(ast: &CallbackStruct) -> TokenStream {
 
             #[doc = #msg]
             #[implement(#interface)]
-            #vis struct #name(::std::cell::UnsafeCell<Option<#closure>>);
+            #vis struct #name(::std::cell::Cell<Option<#closure>>);
 
             impl #name {
                 pub fn create(
@@ -147,18 +338,52 @@ fn impl_completed_callback(ast: &CallbackStruct) -> TokenStream {
                         dyn FnOnce(#interface) -> crate::Result<()>,
                     >,
                     completed: #closure,
+                ) -> crate::Result<()> {
+                    Self::wait_for_async_operation_until(closure, completed, None)
+                }
+
+                pub fn wait_for_async_operation_until(
+                    closure: Box<
+                        dyn FnOnce(#interface) -> crate::Result<()>,
+                    >,
+                    completed: #closure,
+                    deadline: Option<::std::time::Instant>,
                 ) -> crate::Result<()> {
                     let (tx, rx) = ::std::sync::mpsc::channel();
                     let completed: #closure =
                         Box::new(move |arg_1| -> ::windows::core::Result<()> {
-                            let result = completed(arg_1).map_err(crate::Error::WindowsError);
-                            tx.send(result).expect("send over mpsc channel");
+                            let result = completed(arg_1).map_err(crate::Error::from);
+                            // The receiver is gone if the wait already timed out or the message
+                            // loop quit, and then nobody is waiting for the result.
+                            let _ = tx.send(result);
                             Ok(())
                         });
                     let callback = Self::create(completed);
 
                     closure(callback)?;
-                    crate::wait_with_pump(rx)?
+                    crate::wait_with_pump_until(rx, deadline, None)?
+                }
+
+                pub fn future(
+                    closure: Box<
+                        dyn FnOnce(#interface) -> crate::Result<()>,
+                    >,
+                ) -> impl ::std::future::Future<
+                    Output = crate::Result<<#arg_1 as ClosureArg>::Output>,
+                > {
+                    let (tx, rx) = ::futures::channel::oneshot::channel();
+                    let completed: #closure =
+                        Box::new(move |arg_1| -> ::windows::core::Result<()> {
+                            let _ = tx.send(arg_1);
+                            Ok(())
+                        });
+                    let callback = Self::create(completed);
+                    let started = closure(callback);
+
+                    async move {
+                        started?;
+                        rx.await.map_err(|_| crate::Error::TaskCanceled)
+                    }
                 }
             }
 
@@ -168,18 +393,20 @@ fn impl_completed_callback(ast: &CallbackStruct) -> TokenStream {
                     &self,
                     arg_1: <#arg_1 as InvokeArg<'a>>::Input,
                 ) -> ::windows::core::Result<()> {
-                    match unsafe { (*self.0.get()).take() } {
-                        Some(completed) => completed(
-                            <#arg_1 as InvokeArg<'a>>::convert(arg_1),
-                        ),
-                        None => Ok(()),
-                    }
+                    crate::catch_invoke(move || {
+                        match self.0.take() {
+                            Some(completed) => completed(
+                                <#arg_1 as InvokeArg<'a>>::convert(arg_1),
+                            ),
+                            None => Ok(()),
+                        }
+                    })
                 }
             }
         },
     };
 
-    gen.into()
+    Ok(gen)
 }
 
 /// Implement an `EventCallback` using the types specified as tuple struct fields.
@@ -187,43 +414,82 @@ fn impl_completed_callback(ast: &CallbackStruct) -> TokenStream {
 pub fn event_callback(_attr: TokenStream, input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as CallbackStruct);
     impl_event_callback(&ast)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
 }
 
-fn impl_event_callback(ast: &CallbackStruct) -> TokenStream {
+fn impl_event_callback(ast: &CallbackStruct) -> Result<TokenStream2> {
     let vis = &ast.vis;
 
     let name = &ast.ident;
     let closure = get_closure(name);
 
     let interface = &ast.args.interface;
-    let interface = interface
-        .path
-        .segments
-        .last()
-        .expect("event_callback should always specify an interface");
-    let interface_impl = format_ident!("{}_Impl", interface.ident);
+    let interface_impl = format_ident!("{}_Impl", interface);
 
     let arg_1 = &ast.args.arg_1;
-    let arg_2 = &ast
-        .args
-        .arg_2
-        .as_ref()
-        .expect("event_callback should always have 2 arguments");
+    let arg_2 = ast.args.arg_2.as_ref().ok_or_else(|| {
+        Error::new(
+            ast.args.paren,
+            "event handlers take 2 arguments, expected (interface, arg_1, arg_2)",
+        )
+    })?;
 
-    let msg = format!("Implementation of [`{}`].", interface.ident);
+    let msg = format!("Implementation of [`{}`].", interface);
 
     let gen = quote! {
         type #closure = EventClosure<#arg_1, #arg_2>;
 
         #[doc = #msg]
         #[implement(#interface)]
-        #vis struct #name(::std::cell::UnsafeCell<#closure>);
+        #vis struct #name(ReentrantEventClosure<#arg_1, #arg_2>);
 
         impl #name {
             pub fn create(
                 closure: #closure,
             ) -> #interface {
-                Self(closure.into()).into()
+                Self(ReentrantEventClosure::new(closure)).into()
+            }
+
+            pub fn stream(
+                closure: Box<
+                    dyn FnOnce(
+                        #interface,
+                        &mut ::windows::Win32::System::WinRT::EventRegistrationToken,
+                    ) -> crate::Result<()>,
+                >,
+            ) -> crate::Result<(
+                EventStream<#arg_1, #arg_2>,
+                ::windows::Win32::System::WinRT::EventRegistrationToken,
+            )> {
+                let (tx, rx) = ::futures::channel::mpsc::unbounded();
+                let handler = Self::create(Box::new(
+                    move |arg_1, arg_2| -> ::windows::core::Result<()> {
+                        let _ = tx.unbounded_send((arg_1, arg_2));
+                        Ok(())
+                    },
+                ));
+                let mut token = Default::default();
+
+                closure(handler, &mut token)?;
+                Ok((rx, token))
+            }
+
+            pub fn register(
+                closure: #closure,
+                add: Box<
+                    dyn FnOnce(
+                        #interface,
+                        &mut ::windows::Win32::System::WinRT::EventRegistrationToken,
+                    ) -> crate::Result<()>,
+                >,
+                remove: crate::RemoveEventClosure,
+            ) -> crate::Result<crate::EventRegistration> {
+                let handler = Self::create(closure);
+                let mut token = Default::default();
+
+                add(handler, &mut token)?;
+                Ok(crate::EventRegistration::new(token, remove))
             }
         }
 
@@ -234,15 +500,17 @@ fn impl_event_callback(ast: &CallbackStruct) -> TokenStream {
                 arg_1: <#arg_1 as InvokeArg<'a>>::Input,
                 arg_2: <#arg_2 as InvokeArg<'a>>::Input,
             ) -> ::windows::core::Result<()> {
-                unsafe { (*self.0.get())(
-                    <#arg_1 as InvokeArg<'a>>::convert(arg_1),
-                    <#arg_2 as InvokeArg<'a>>::convert(arg_2),
-                ) }
+                crate::catch_invoke(move || {
+                    self.0.invoke(
+                        <#arg_1 as InvokeArg<'a>>::convert(arg_1),
+                        <#arg_2 as InvokeArg<'a>>::convert(arg_2),
+                    )
+                })
             }
         }
     };
 
-    gen.into()
+    Ok(gen)
 }
 
 fn get_closure(name: &Ident) -> Ident {
@@ -0,0 +1,10 @@
+use webview2_com_macros::callback;
+
+#[callback(completed)]
+pub struct NavigationStartingEventHandler(
+    ICoreWebView2NavigationStartingEventHandler,
+    Option<ICoreWebView2>,
+    Option<ICoreWebView2NavigationStartingEventArgs>,
+);
+
+fn main() {}
@@ -0,0 +1,9 @@
+use webview2_com_macros::event_callback;
+
+#[event_callback]
+pub struct NavigationStartingEventHandler(
+    ICoreWebView2NavigationStartingEventHandler,
+    Option<ICoreWebView2>,
+);
+
+fn main() {}
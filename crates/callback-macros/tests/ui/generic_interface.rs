@@ -0,0 +1,10 @@
+use webview2_com_macros::completed_callback;
+
+#[completed_callback]
+pub struct ExecuteScriptCompletedHandler(
+    Option<ICoreWebView2ExecuteScriptCompletedHandler>,
+    HRESULT,
+    PCWSTR,
+);
+
+fn main() {}
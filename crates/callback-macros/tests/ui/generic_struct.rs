@@ -0,0 +1,10 @@
+use webview2_com_macros::callback;
+
+#[callback]
+pub struct ExecuteScriptCompletedHandler<T>(
+    ICoreWebView2ExecuteScriptCompletedHandler,
+    HRESULT,
+    T,
+);
+
+fn main() {}
@@ -0,0 +1,6 @@
+use webview2_com_macros::callback;
+
+#[callback]
+pub struct ExecuteScriptCompleted(ICoreWebView2ExecuteScriptCompleted, HRESULT, PCWSTR);
+
+fn main() {}
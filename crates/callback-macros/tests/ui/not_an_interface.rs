@@ -0,0 +1,6 @@
+use webview2_com_macros::callback;
+
+#[callback]
+pub struct ExecuteScriptCompletedHandler(CoreWebView2ExecuteScriptCompletedHandler, HRESULT, PCWSTR);
+
+fn main() {}
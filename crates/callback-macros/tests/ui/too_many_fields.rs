@@ -0,0 +1,11 @@
+use webview2_com_macros::callback;
+
+#[callback]
+pub struct ExecuteScriptCompletedHandler(
+    ICoreWebView2ExecuteScriptCompletedHandler,
+    HRESULT,
+    PCWSTR,
+    BOOL,
+);
+
+fn main() {}
@@ -0,0 +1,6 @@
+use webview2_com_macros::callback;
+
+#[callback(once)]
+pub struct ExecuteScriptCompletedHandler(ICoreWebView2ExecuteScriptCompletedHandler, HRESULT, PCWSTR);
+
+fn main() {}
@@ -0,0 +1,430 @@
+use std::{fmt, iter, mem, str::FromStr};
+
+use crate::{Error, Result};
+
+/// Switches which hold a comma-separated list of features, paired with the switch that
+/// cancels each feature in the list.
+const FEATURE_LISTS: &[(&str, &str)] = &[
+    ("enable-features", "disable-features"),
+    ("disable-features", "enable-features"),
+    ("enable-blink-features", "disable-blink-features"),
+    ("disable-blink-features", "enable-blink-features"),
+];
+
+/// Structured model of the Chromium switches passed to the browser process through
+/// `AdditionalBrowserArguments`, e.g. `--enable-features=A,B --proxy-server="host:8080"`.
+///
+/// Each switch appears once, in the order it was first added. Setting a switch again replaces its
+/// value, except for the feature lists (`--enable-features`, `--disable-features`, and their
+/// `blink` counterparts), which are merged and deduplicated instead. Enabling a feature removes it
+/// from the matching disable list and vice versa, so the source which is merged last wins.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BrowserArguments {
+    switches: Vec<(String, Option<String>)>,
+}
+
+impl BrowserArguments {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Parse a command line using the same quoting rules as `CommandLineToArgvW`. Every argument
+    /// must be a switch which starts with `--`.
+    pub fn parse(command_line: &str) -> Result<Self> {
+        let mut arguments = Self::new();
+
+        for token in split_command_line(command_line)? {
+            let switch = token.strip_prefix("--").ok_or_else(|| {
+                Error::InvalidArguments(format!("expected a switch starting with --: {}", token))
+            })?;
+            let (name, value) = match switch.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (switch, None),
+            };
+            if name.is_empty() {
+                return Err(Error::InvalidArguments(format!(
+                    "missing switch name: {}",
+                    token
+                )));
+            }
+
+            arguments.insert(name, value);
+        }
+
+        Ok(arguments)
+    }
+
+    /// Set a switch, with or without a value. The name may include the leading `--`, and it is
+    /// compared without regard to ASCII case, like Chromium does on Windows.
+    pub fn insert(&mut self, name: &str, value: Option<&str>) {
+        let name = normalize_name(name);
+
+        match feature_list(&name) {
+            Some(opposite) => {
+                let mut features = value.into_iter().flat_map(split_features).peekable();
+                if features.peek().is_none() {
+                    // Keep a feature list switch without any features, instead of dropping it.
+                    if self.position(&name).is_none() {
+                        self.switches.push((name, value.map(|_| String::new())));
+                    }
+                    return;
+                }
+
+                for feature in features {
+                    self.remove_feature(opposite, feature);
+                    self.add_feature(&name, feature);
+                }
+            }
+            None => {
+                let value = value.map(str::to_string);
+                match self.position(&name) {
+                    Some(index) => self.switches[index].1 = value,
+                    None => self.switches.push((name, value)),
+                }
+            }
+        }
+    }
+
+    /// Remove a switch, and report whether it was set.
+    pub fn remove(&mut self, name: &str) -> bool {
+        match self.position(&normalize_name(name)) {
+            Some(index) => {
+                self.switches.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Check if a switch is set, with or without a value.
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(&normalize_name(name)).is_some()
+    }
+
+    /// Get the value of a switch, if it is set and has one.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.position(&normalize_name(name))
+            .and_then(|index| self.switches[index].1.as_deref())
+    }
+
+    /// Add a feature to `--enable-features`, and remove it from `--disable-features`.
+    pub fn enable_feature(&mut self, feature: &str) {
+        self.insert("enable-features", Some(feature));
+    }
+
+    /// Add a feature to `--disable-features`, and remove it from `--enable-features`.
+    pub fn disable_feature(&mut self, feature: &str) {
+        self.insert("disable-features", Some(feature));
+    }
+
+    /// Apply every switch from `other` on top of these, as if they were inserted one at a time.
+    pub fn merge(&mut self, other: &BrowserArguments) {
+        for (name, value) in other.iter() {
+            self.insert(name, value);
+        }
+    }
+
+    /// Iterate over the switch names, without the leading `--`, and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.switches
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_deref()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.switches.is_empty()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.switches.iter().position(|(switch, _)| switch == name)
+    }
+
+    fn add_feature(&mut self, name: &str, feature: &str) {
+        match self.position(name) {
+            Some(index) => {
+                let value = self.switches[index].1.get_or_insert_with(String::new);
+                if !split_features(value).any(|existing| existing == feature) {
+                    if !value.is_empty() {
+                        value.push(',');
+                    }
+                    value.push_str(feature);
+                }
+            }
+            None => self
+                .switches
+                .push((name.to_string(), Some(feature.to_string()))),
+        }
+    }
+
+    fn remove_feature(&mut self, name: &str, feature: &str) {
+        if let Some(index) = self.position(name) {
+            let value = self.switches[index].1.as_deref().unwrap_or_default();
+            if !split_features(value).any(|existing| existing == feature) {
+                return;
+            }
+
+            let remaining = split_features(value)
+                .filter(|existing| *existing != feature)
+                .collect::<Vec<_>>()
+                .join(",");
+
+            if remaining.is_empty() {
+                self.switches.remove(index);
+            } else {
+                self.switches[index].1 = Some(remaining);
+            }
+        }
+    }
+}
+
+impl FromStr for BrowserArguments {
+    type Err = Error;
+
+    fn from_str(command_line: &str) -> Result<Self> {
+        Self::parse(command_line)
+    }
+}
+
+impl fmt::Display for BrowserArguments {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, (name, value)) in self.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+
+            write!(f, "--{}", name)?;
+            if let Some(value) = value {
+                write!(f, "={}", quote_value(value))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BrowserArguments {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let command_line = String::deserialize(deserializer)?;
+        Self::parse(&command_line).map_err(serde::de::Error::custom)
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.trim_start_matches('-').to_ascii_lowercase()
+}
+
+fn feature_list(name: &str) -> Option<&'static str> {
+    FEATURE_LISTS
+        .iter()
+        .find(|(list, _)| *list == name)
+        .map(|(_, opposite)| *opposite)
+}
+
+fn split_features(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|feature| !feature.is_empty())
+}
+
+/// Split a command line into arguments using the rules from `CommandLineToArgvW`: whitespace
+/// outside of double quotes separates arguments, `2n` backslashes before a quote become `n`
+/// backslashes and toggle quoting, `2n + 1` backslashes before a quote become `n` backslashes and
+/// a literal quote, and any other backslashes are kept as they are.
+fn split_command_line(command_line: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quoted = false;
+    let mut backslashes = 0;
+
+    for c in command_line.chars() {
+        match c {
+            '\\' => {
+                backslashes += 1;
+                in_token = true;
+            }
+            '"' => {
+                current.extend(iter::repeat('\\').take(backslashes / 2));
+                if backslashes % 2 == 1 {
+                    current.push('"');
+                } else {
+                    quoted = !quoted;
+                }
+                backslashes = 0;
+                in_token = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                current.extend(iter::repeat('\\').take(mem::take(&mut backslashes)));
+                if mem::take(&mut in_token) {
+                    tokens.push(mem::take(&mut current));
+                }
+            }
+            c => {
+                current.extend(iter::repeat('\\').take(mem::take(&mut backslashes)));
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+
+    if quoted {
+        return Err(Error::InvalidArguments(format!(
+            "unterminated quote in: {}",
+            command_line
+        )));
+    }
+
+    current.extend(iter::repeat('\\').take(backslashes));
+    if in_token {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+/// Quote a switch value if it is empty or contains whitespace or quotes, so that
+/// [`split_command_line`] returns the same value.
+fn quote_value(value: &str) -> String {
+    if !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '"') {
+        return value.to_string();
+    }
+
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+
+    for c in value.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.extend(iter::repeat('\\').take(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            c => {
+                quoted.extend(iter::repeat('\\').take(mem::take(&mut backslashes)));
+                quoted.push(c);
+            }
+        }
+    }
+
+    quoted.extend(iter::repeat('\\').take(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_switches() {
+        let arguments = BrowserArguments::parse(
+            r#"--disable-gpu --proxy-server="http://host:8080" --remote-debugging-port=9222"#,
+        )
+        .unwrap();
+        assert_eq!(
+            arguments.iter().collect::<Vec<_>>(),
+            vec![
+                ("disable-gpu", None),
+                ("proxy-server", Some("http://host:8080")),
+                ("remote-debugging-port", Some("9222")),
+            ]
+        );
+        assert!(arguments.contains("--disable-gpu"));
+        assert_eq!(arguments.value("Remote-Debugging-Port"), Some("9222"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(
+            BrowserArguments::parse("disable-gpu"),
+            Err(Error::InvalidArguments(_))
+        ));
+        assert!(matches!(
+            BrowserArguments::parse(r#"--user-agent="unterminated"#),
+            Err(Error::InvalidArguments(_))
+        ));
+        assert!(matches!(
+            BrowserArguments::parse("--=value"),
+            Err(Error::InvalidArguments(_))
+        ));
+    }
+
+    #[test]
+    fn dedupe_features() {
+        let arguments: BrowserArguments =
+            "--enable-features=A,B --enable-features=B,C --disable-features=D"
+                .parse()
+                .unwrap();
+        assert_eq!(arguments.value("enable-features"), Some("A,B,C"));
+        assert_eq!(arguments.value("disable-features"), Some("D"));
+    }
+
+    #[test]
+    fn merge_last_wins() {
+        let mut arguments: BrowserArguments =
+            "--enable-features=A,B --disable-features=C --proxy-server=first"
+                .parse()
+                .unwrap();
+        let overrides: BrowserArguments =
+            "--disable-features=A --enable-features=C --proxy-server=second"
+                .parse()
+                .unwrap();
+        arguments.merge(&overrides);
+
+        assert_eq!(
+            arguments.to_string(),
+            "--enable-features=B,C --disable-features=A --proxy-server=second"
+        );
+    }
+
+    #[test]
+    fn toggle_features() {
+        let mut arguments = BrowserArguments::new();
+        arguments.enable_feature("A");
+        arguments.disable_feature("A");
+        assert!(!arguments.contains("enable-features"));
+        assert_eq!(arguments.value("disable-features"), Some("A"));
+        assert!(arguments.remove("disable-features"));
+        assert!(arguments.is_empty());
+    }
+
+    #[test]
+    fn empty_feature_lists() {
+        let mut arguments: BrowserArguments =
+            "--enable-features --disable-features=".parse().unwrap();
+        assert!(arguments.contains("enable-features"));
+        assert_eq!(arguments.value("enable-features"), None);
+        assert_eq!(arguments.value("disable-features"), Some(""));
+        let command_line = arguments.to_string();
+        assert_eq!(command_line, r#"--enable-features --disable-features="""#);
+        assert_eq!(BrowserArguments::parse(&command_line).unwrap(), arguments);
+
+        arguments.insert("enable-features", Some("A"));
+        arguments.insert("disable-features", None);
+        assert_eq!(arguments.value("enable-features"), Some("A"));
+        assert_eq!(arguments.value("disable-features"), Some(""));
+        arguments.disable_feature("A");
+        assert!(!arguments.contains("enable-features"));
+        assert_eq!(arguments.value("disable-features"), Some("A"));
+    }
+
+    #[test]
+    fn quote_round_trip() {
+        let mut arguments = BrowserArguments::new();
+        arguments.insert("user-agent", Some(r#"Fake "Agent" 1.0"#));
+        arguments.insert("user-data-dir", Some(r#"C:\Fake Data\"#));
+        arguments.insert("lang", Some(""));
+        let command_line = arguments.to_string();
+        assert_eq!(
+            command_line,
+            r#"--user-agent="Fake \"Agent\" 1.0" --user-data-dir="C:\Fake Data\\" --lang="""#
+        );
+        assert_eq!(BrowserArguments::parse(&command_line).unwrap(), arguments);
+    }
+}
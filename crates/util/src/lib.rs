@@ -0,0 +1,34 @@
+mod arguments;
+mod pwstr;
+mod version;
+
+use std::fmt;
+
+pub use arguments::*;
+pub use pwstr::*;
+pub use version::*;
+
+/// Errors returned by this crate. They convert to the matching variants of `webview2_com::Error`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    InvalidArguments(String),
+    InvalidVersion(String),
+    InvalidString(String),
+    /// The [`ComAllocator`] returned a null pointer.
+    OutOfMemory,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidArguments(message) => write!(f, "invalid browser arguments: {}", message),
+            Error::InvalidVersion(version) => write!(f, "invalid browser version: {}", version),
+            Error::InvalidString(message) => write!(f, "invalid string: {}", message),
+            Error::OutOfMemory => f.write_str("out of memory"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,468 @@
+use std::{
+    alloc::{self, Layout},
+    ffi::{c_void, OsStr, OsString},
+    fmt::Display,
+    marker::PhantomData,
+    mem, ptr, slice,
+};
+#[cfg(any(test, feature = "test-util"))]
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
+
+use windows::core::{PCWSTR, PWSTR};
+
+use crate::{Error, Result};
+
+/// Allocator which owns the buffer behind a [`ComString`].
+///
+/// # Safety
+///
+/// `free` must accept any non-null pointer returned by `alloc`, and `alloc` must return null or
+/// a buffer of at least `size` bytes which is aligned for `u16`.
+pub unsafe trait ComAllocator {
+    fn alloc(size: usize) -> *mut c_void;
+
+    /// # Safety
+    ///
+    /// `buffer` must be a non-null pointer from `alloc` which has not been freed yet.
+    unsafe fn free(buffer: *mut c_void);
+}
+
+/// RAII holder for a [`PWSTR`] which is allocated with `A::alloc` and freed with `A::free` when
+/// dropped. WebView2 expects `CoTaskMemAllocator` from `webview2-com` for every string
+/// out-param.
+pub struct ComString<'a, A: ComAllocator>(PWSTR, PhantomData<(&'a PWSTR, A)>);
+
+/// Constant guard object tied to the lifetime of the [`ComString`] so that it
+/// is safe to dereference the [`PCWSTR`] as long as both are still in scope.
+pub struct CoTaskMemRef<'a>(PCWSTR, PhantomData<&'a PCWSTR>);
+
+impl<'a> CoTaskMemRef<'a> {
+    pub fn as_pcwstr(&self) -> &PCWSTR {
+        &self.0
+    }
+}
+
+impl<'a, 'b, A: ComAllocator> From<&'b ComString<'a, A>> for CoTaskMemRef<'b> {
+    fn from(value: &'b ComString<'a, A>) -> Self {
+        Self(PCWSTR(value.0 .0), PhantomData)
+    }
+}
+
+/// Mutable guard object tied to the lifetime of the [`ComString`] so that it
+/// is safe to dereference the [`PWSTR`] as long as both are still in scope.
+pub struct CoTaskMemMut<'a>(&'a PWSTR);
+
+impl<'a> CoTaskMemMut<'a> {
+    pub fn as_pwstr(&mut self) -> &'a PWSTR {
+        self.0
+    }
+}
+
+impl<'a, 'b, A: ComAllocator> From<&'b mut ComString<'a, A>> for CoTaskMemMut<'b> {
+    fn from(value: &'b mut ComString<'a, A>) -> Self {
+        Self(&value.0)
+    }
+}
+
+impl<'a, A: ComAllocator> ComString<'a, A> {
+    /// Get a mutable [`PWSTR`] guard which borrows the pointer.
+    pub fn as_mut(&mut self) -> CoTaskMemMut<'_> {
+        From::from(self)
+    }
+
+    /// Get a constant [`PCWSTR`] guard which borrows the pointer.
+    pub fn as_ref(&self) -> CoTaskMemRef<'_> {
+        From::from(self)
+    }
+
+    /// Take the [`PWSTR`] pointer and hand off ownership so that it is not freed when the `ComString` is dropped.
+    pub fn take(&mut self) -> PWSTR {
+        let result = self.0;
+        self.0 .0 = ptr::null_mut();
+        result
+    }
+
+    /// Allocate a copy of a [`&str`] with the same rules as `From<&str>`, but return
+    /// [`Error::OutOfMemory`] instead of aborting if the allocation fails.
+    pub fn new(value: &str) -> Result<Self> {
+        match value {
+            "" => Ok(Default::default()),
+            value => Self::alloc_wide(&value.encode_utf16().collect::<Vec<_>>()),
+        }
+    }
+
+    /// Allocate a copy of a [`&str`]. Unlike `From<&str>`, an empty string is allocated instead
+    /// of returning a null pointer, and a string with an interior NUL is rejected instead of
+    /// being cut short.
+    pub fn try_from_str(value: &str) -> Result<Self> {
+        Self::from_wide(&value.encode_utf16().collect::<Vec<_>>())
+    }
+
+    /// Allocate a copy of an [`OsStr`]. On Windows this keeps unpaired surrogates. Elsewhere the
+    /// value must be valid Unicode.
+    pub fn try_from_os_str(value: &OsStr) -> Result<Self> {
+        #[cfg(windows)]
+        let encoded: Vec<_> = std::os::windows::ffi::OsStrExt::encode_wide(value).collect();
+        #[cfg(not(windows))]
+        let encoded: Vec<_> = value
+            .to_str()
+            .ok_or_else(|| Error::InvalidString(format!("not valid Unicode: {:?}", value)))?
+            .encode_utf16()
+            .collect();
+
+        Self::from_wide(&encoded)
+    }
+
+    /// Allocate a copy of some UTF-16 code units, which are not required to be valid UTF-16.
+    /// `value` should not include the NUL terminator, and it must not contain any other NULs.
+    pub fn from_wide(value: &[u16]) -> Result<Self> {
+        if let Some(index) = value.iter().position(|&c| c == 0) {
+            return Err(Error::InvalidString(format!(
+                "interior NUL at index {}",
+                index
+            )));
+        }
+
+        Self::alloc_wide(value)
+    }
+
+    /// Borrow the UTF-16 code units, not including the NUL terminator. A null pointer is empty.
+    pub fn as_wide(&self) -> &[u16] {
+        unsafe { wide_from_ptr(self.0 .0) }
+    }
+
+    /// Copy the string to a [`String`], or return an error if it is not valid UTF-16.
+    pub fn try_to_string(&self) -> Result<String> {
+        String::from_utf16(self.as_wide())
+            .map_err(|_| Error::InvalidString(format!("not valid UTF-16: {:?}", self.as_wide())))
+    }
+
+    /// Copy the string to an [`OsString`]. On Windows this is lossless. Elsewhere any unpaired
+    /// surrogates are replaced with `U+FFFD`.
+    pub fn to_os_string(&self) -> OsString {
+        #[cfg(windows)]
+        return std::os::windows::ffi::OsStringExt::from_wide(self.as_wide());
+        #[cfg(not(windows))]
+        return String::from_utf16_lossy(self.as_wide()).into();
+    }
+
+    fn alloc_wide(value: &[u16]) -> Result<Self> {
+        unsafe {
+            let buffer = A::alloc((value.len() + 1) * mem::size_of::<u16>()) as *mut u16;
+            if buffer.is_null() {
+                return Err(Error::OutOfMemory);
+            }
+
+            ptr::copy_nonoverlapping(value.as_ptr(), buffer, value.len());
+            *buffer.add(value.len()) = 0;
+            Ok(Self(PWSTR(buffer), PhantomData))
+        }
+    }
+}
+
+impl<'a, A: ComAllocator> Drop for ComString<'a, A> {
+    fn drop(&mut self) {
+        if !self.0 .0.is_null() {
+            unsafe {
+                A::free(self.0 .0 as *mut _);
+            }
+            self.0 .0 = ptr::null_mut();
+        }
+    }
+}
+
+impl<'a, A: ComAllocator> Default for ComString<'a, A> {
+    fn default() -> Self {
+        Self(PWSTR(ptr::null_mut()), PhantomData)
+    }
+}
+
+impl<'a, A: ComAllocator> From<PWSTR> for ComString<'a, A> {
+    fn from(value: PWSTR) -> Self {
+        Self(value, PhantomData)
+    }
+}
+
+/// An empty string is a null pointer. If the allocation fails, this calls
+/// [`alloc::handle_alloc_error`], which aborts rather than unwinding into a COM caller. Use
+/// [`ComString::new`] to handle it instead.
+impl<'a, A: ComAllocator> From<&str> for ComString<'a, A> {
+    fn from(value: &str) -> Self {
+        Self::new(value).unwrap_or_else(|_| {
+            let len = value.encode_utf16().count() + 1;
+            alloc::handle_alloc_error(
+                Layout::array::<u16>(len).unwrap_or_else(|_| Layout::new::<u16>()),
+            )
+        })
+    }
+}
+
+impl<'a, A: ComAllocator> Display for ComString<'a, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = string_from_pcwstr(self.as_ref().as_pcwstr());
+        f.write_str(value.as_str())
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+thread_local! {
+    static LIVE: RefCell<HashMap<usize, Layout>> = RefCell::new(HashMap::new());
+    static COUNTS: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+}
+
+/// Allocator which uses [`std::alloc`] and counts the allocations and frees on the current
+/// thread, e.g. to check that a COM out-param is freed exactly once. It panics if a buffer is
+/// freed twice or was never allocated by it on the same thread.
+///
+/// This is only for tests, so it needs the `test-util` feature.
+#[cfg(any(test, feature = "test-util"))]
+pub struct CountingAllocator;
+
+#[cfg(any(test, feature = "test-util"))]
+impl CountingAllocator {
+    /// Reset the counts for the current thread.
+    pub fn reset() {
+        COUNTS.with(|counts| counts.set((0, 0)));
+    }
+
+    /// Number of (allocations, frees) on the current thread since the last reset.
+    pub fn counts() -> (usize, usize) {
+        COUNTS.with(Cell::get)
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+unsafe impl ComAllocator for CountingAllocator {
+    fn alloc(size: usize) -> *mut c_void {
+        let layout = match Layout::from_size_align(size.max(1), mem::align_of::<u16>()) {
+            Ok(layout) => layout,
+            Err(_) => return ptr::null_mut(),
+        };
+        let buffer = unsafe { alloc::alloc(layout) };
+        if !buffer.is_null() {
+            LIVE.with(|live| live.borrow_mut().insert(buffer as usize, layout));
+            COUNTS.with(|counts| counts.set((counts.get().0 + 1, counts.get().1)));
+        }
+        buffer as *mut _
+    }
+
+    unsafe fn free(buffer: *mut c_void) {
+        let layout = LIVE
+            .with(|live| live.borrow_mut().remove(&(buffer as usize)))
+            .expect("freed a buffer which is not live");
+        COUNTS.with(|counts| counts.set((counts.get().0, counts.get().1 + 1)));
+        alloc::dealloc(buffer as *mut _, layout);
+    }
+}
+
+/// Borrow the UTF-16 code units before the NUL terminator. A null pointer is empty.
+///
+/// # Safety
+///
+/// `source` must be null or point to a NUL-terminated string which outlives the result.
+unsafe fn wide_from_ptr<'a>(source: *const u16) -> &'a [u16] {
+    if source.is_null() {
+        &[]
+    } else {
+        let mut len = 0;
+        while *source.add(len) != 0 {
+            len += 1;
+        }
+        slice::from_raw_parts(source, len)
+    }
+}
+
+/// Copy a [`PCWSTR`] from an input param to a [`String`], replacing any unpaired surrogates
+/// with `U+FFFD`. Use [`try_string_from_pcwstr`] to reject them instead.
+pub fn string_from_pcwstr(source: &PCWSTR) -> String {
+    String::from_utf16_lossy(unsafe { wide_from_ptr(source.0) })
+}
+
+/// Copy a [`PCWSTR`] from an input param to a [`String`], or return an error if it is not
+/// valid UTF-16.
+pub fn try_string_from_pcwstr(source: &PCWSTR) -> Result<String> {
+    let wide = unsafe { wide_from_ptr(source.0) };
+    String::from_utf16(wide)
+        .map_err(|_| Error::InvalidString(format!("not valid UTF-16: {:?}", wide)))
+}
+
+/// Copy a [`PCWSTR`] from an input param to an [`OsString`]. On Windows this is lossless.
+/// Elsewhere any unpaired surrogates are replaced with `U+FFFD`.
+pub fn os_string_from_pcwstr(source: &PCWSTR) -> OsString {
+    let wide = unsafe { wide_from_ptr(source.0) };
+    #[cfg(windows)]
+    return std::os::windows::ffi::OsStringExt::from_wide(wide);
+    #[cfg(not(windows))]
+    return String::from_utf16_lossy(wide).into();
+}
+
+/// Copy a [`PWSTR`] which was allocated with `A` from an input param to a [`String`] and free
+/// the original buffer with `A`.
+pub fn take_pwstr_in<A: ComAllocator>(source: PWSTR) -> String {
+    ComString::<A>::from(source).to_string()
+}
+
+/// Like [`take_pwstr_in`], but return an error if it is not valid UTF-16. The buffer is freed
+/// either way.
+pub fn try_take_pwstr_in<A: ComAllocator>(source: PWSTR) -> Result<String> {
+    ComString::<A>::from(source).try_to_string()
+}
+
+/// Allocate a [`PWSTR`] with `A` and copy a [`&str`] into it, using the rules from
+/// `From<&str>` for [`ComString`].
+pub fn pwstr_from_str_in<A: ComAllocator>(source: &str) -> PWSTR {
+    ComString::<A>::from(source).take()
+}
+
+/// Allocate a [`PWSTR`] with `A` and copy a [`&str`] into it, using the strict rules from
+/// [`ComString::try_from_str`].
+pub fn try_pwstr_from_str_in<A: ComAllocator>(source: &str) -> Result<PWSTR> {
+    Ok(ComString::<A>::try_from_str(source)?.take())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Allocator which always fails.
+    struct FailingAllocator;
+
+    unsafe impl ComAllocator for FailingAllocator {
+        fn alloc(_size: usize) -> *mut c_void {
+            ptr::null_mut()
+        }
+
+        unsafe fn free(_buffer: *mut c_void) {
+            unreachable!("nothing was allocated");
+        }
+    }
+
+    #[test]
+    fn out_param_freed_once() {
+        CountingAllocator::reset();
+        let pwstr = pwstr_from_str_in::<CountingAllocator>("Fake Value");
+        assert_eq!(CountingAllocator::counts(), (1, 0));
+        assert_eq!(&take_pwstr_in::<CountingAllocator>(pwstr), "Fake Value");
+        assert_eq!(CountingAllocator::counts(), (1, 1));
+
+        assert!(pwstr_from_str_in::<CountingAllocator>("").is_null());
+        assert_eq!(&take_pwstr_in::<CountingAllocator>(PWSTR::null()), "");
+        assert_eq!(CountingAllocator::counts(), (1, 1));
+    }
+
+    #[test]
+    fn take_hands_off_ownership() {
+        CountingAllocator::reset();
+        let mut value = ComString::<CountingAllocator>::from("Fake Value");
+        let pwstr = value.take();
+        assert!(value.as_wide().is_empty());
+        drop(value);
+        assert_eq!(CountingAllocator::counts(), (1, 0));
+
+        drop(ComString::<CountingAllocator>::from(pwstr));
+        assert_eq!(CountingAllocator::counts(), (1, 1));
+    }
+
+    #[test]
+    fn guards_do_not_free() {
+        CountingAllocator::reset();
+        let mut value = ComString::<CountingAllocator>::try_from_str("Fake Value").unwrap();
+        {
+            let guard = value.as_ref();
+            assert_eq!(&string_from_pcwstr(guard.as_pcwstr()), "Fake Value");
+        }
+        assert_eq!(CountingAllocator::counts(), (1, 0));
+        {
+            let mut guard = value.as_mut();
+            assert!(!guard.as_pwstr().is_null());
+        }
+        assert_eq!(CountingAllocator::counts(), (1, 0));
+        assert_eq!(&value.to_string(), "Fake Value");
+        drop(value);
+        assert_eq!(CountingAllocator::counts(), (1, 1));
+    }
+
+    #[test]
+    fn out_of_memory() {
+        assert_eq!(
+            ComString::<FailingAllocator>::new("Fake Value").err(),
+            Some(Error::OutOfMemory)
+        );
+        assert_eq!(
+            try_pwstr_from_str_in::<FailingAllocator>("Fake Value"),
+            Err(Error::OutOfMemory)
+        );
+        assert!(ComString::<FailingAllocator>::new("").is_ok());
+    }
+
+    #[test]
+    fn empty_strings() {
+        assert!(pwstr_from_str_in::<CountingAllocator>("").is_null());
+        assert_eq!(&take_pwstr_in::<CountingAllocator>(PWSTR::null()), "");
+
+        let empty = ComString::<CountingAllocator>::try_from_str("").unwrap();
+        assert!(!empty.as_ref().as_pcwstr().is_null());
+        assert!(empty.as_wide().is_empty());
+        assert_eq!(&empty.try_to_string().unwrap(), "");
+    }
+
+    #[test]
+    fn reject_interior_nul() {
+        assert!(matches!(
+            ComString::<CountingAllocator>::try_from_str("Fake\0Value"),
+            Err(Error::InvalidString(_))
+        ));
+        assert!(matches!(
+            ComString::<CountingAllocator>::from_wide(&[0x46, 0, 0x46]),
+            Err(Error::InvalidString(_))
+        ));
+    }
+
+    #[test]
+    fn reject_unpaired_surrogate() {
+        let value = ComString::<CountingAllocator>::from_wide(&[0x46, 0xD800, 0x46]).unwrap();
+        assert_eq!(value.as_wide(), &[0x46, 0xD800, 0x46]);
+        assert!(matches!(
+            value.try_to_string(),
+            Err(Error::InvalidString(_))
+        ));
+        assert_eq!(&value.to_string(), "F\u{FFFD}F");
+
+        let source = PCWSTR(value.as_wide().as_ptr());
+        assert!(try_string_from_pcwstr(&source).is_err());
+        assert_eq!(&string_from_pcwstr(&source), "F\u{FFFD}F");
+    }
+
+    #[test]
+    fn strict_round_trip() {
+        let value = "Fake \u{1F600} Value";
+        let pwstr = try_pwstr_from_str_in::<CountingAllocator>(value).unwrap();
+        assert_eq!(&try_string_from_pcwstr(&PCWSTR(pwstr.0)).unwrap(), value);
+        assert_eq!(
+            &try_take_pwstr_in::<CountingAllocator>(pwstr).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn os_string_round_trip() {
+        let value = OsStr::new("Fake Value");
+        let pwstr = ComString::<CountingAllocator>::try_from_os_str(value).unwrap();
+        assert_eq!(pwstr.to_os_string(), value);
+        assert_eq!(os_string_from_pcwstr(pwstr.as_ref().as_pcwstr()), value);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn os_string_keeps_surrogates() {
+        use std::os::windows::ffi::OsStringExt;
+
+        let value = OsString::from_wide(&[0x46, 0xD800, 0x46]);
+        let pwstr = ComString::<CountingAllocator>::try_from_os_str(&value).unwrap();
+        assert_eq!(pwstr.as_wide(), &[0x46, 0xD800, 0x46]);
+        assert_eq!(pwstr.to_os_string(), value);
+    }
+}
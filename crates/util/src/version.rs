@@ -0,0 +1,256 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use webview2_com_sys::Microsoft::Web::WebView2::Win32::CORE_WEBVIEW_TARGET_PRODUCT_VERSION;
+
+use crate::{Error, Result};
+
+/// Release channel which is appended to the version string of a pre-release WebView2 Runtime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BrowserChannel {
+    Stable,
+    Beta,
+    Dev,
+    Canary,
+}
+
+impl BrowserChannel {
+    fn suffix(self) -> Option<&'static str> {
+        match self {
+            BrowserChannel::Stable => None,
+            BrowserChannel::Beta => Some("beta"),
+            BrowserChannel::Dev => Some("dev"),
+            BrowserChannel::Canary => Some("canary"),
+        }
+    }
+}
+
+/// Parsed browser version string, e.g. `104.0.1293.44` or `105.0.1343.4 canary`.
+///
+/// Like `CompareBrowserVersions`, the comparisons only look at the four numeric parts and ignore
+/// the channel, so `104.0.1293.44 beta` is equal to `104.0.1293.44`. Use
+/// [`BrowserVersion::channel`] to tell them apart.
+#[derive(Clone, Copy, Debug)]
+pub struct BrowserVersion {
+    parts: [u32; 4],
+    channel: BrowserChannel,
+}
+
+impl BrowserVersion {
+    pub fn new(major: u32, minor: u32, build: u32, patch: u32, channel: BrowserChannel) -> Self {
+        Self {
+            parts: [major, minor, build, patch],
+            channel,
+        }
+    }
+
+    /// Parse a version string returned by `GetAvailableCoreWebView2BrowserVersionString` or
+    /// `ICoreWebView2Environment::BrowserVersionString`.
+    pub fn parse(version: &str) -> Result<Self> {
+        let invalid = || Error::InvalidVersion(version.to_string());
+        let mut words = version.split_whitespace();
+        let numbers = words.next().ok_or_else(invalid)?;
+
+        let channel = match words.next() {
+            None => BrowserChannel::Stable,
+            Some(channel) if channel.eq_ignore_ascii_case("beta") => BrowserChannel::Beta,
+            Some(channel) if channel.eq_ignore_ascii_case("dev") => BrowserChannel::Dev,
+            Some(channel) if channel.eq_ignore_ascii_case("canary") => BrowserChannel::Canary,
+            Some(_) => return Err(invalid()),
+        };
+        if words.next().is_some() {
+            return Err(invalid());
+        }
+
+        let mut parts = [0; 4];
+        let mut numbers = numbers.split('.');
+        for part in parts.iter_mut() {
+            *part = numbers
+                .next()
+                .and_then(|number| number.parse().ok())
+                .ok_or_else(invalid)?;
+        }
+        if numbers.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Self { parts, channel })
+    }
+
+    /// Version of the WebView2 SDK which the bindings were generated from, i.e.
+    /// [`CORE_WEBVIEW_TARGET_PRODUCT_VERSION`].
+    pub fn target_product_version() -> Self {
+        Self::parse(CORE_WEBVIEW_TARGET_PRODUCT_VERSION)
+            .expect("CORE_WEBVIEW_TARGET_PRODUCT_VERSION should be a valid version")
+    }
+
+    pub fn major(&self) -> u32 {
+        self.parts[0]
+    }
+
+    pub fn minor(&self) -> u32 {
+        self.parts[1]
+    }
+
+    pub fn build(&self) -> u32 {
+        self.parts[2]
+    }
+
+    pub fn patch(&self) -> u32 {
+        self.parts[3]
+    }
+
+    pub fn channel(&self) -> BrowserChannel {
+        self.channel
+    }
+}
+
+impl PartialEq for BrowserVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.parts == other.parts
+    }
+}
+
+impl Eq for BrowserVersion {}
+
+impl PartialOrd for BrowserVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BrowserVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.parts.cmp(&other.parts)
+    }
+}
+
+impl FromStr for BrowserVersion {
+    type Err = Error;
+
+    fn from_str(version: &str) -> Result<Self> {
+        Self::parse(version)
+    }
+}
+
+impl fmt::Display for BrowserVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [major, minor, build, patch] = self.parts;
+        write!(f, "{}.{}.{}.{}", major, minor, build, patch)?;
+        if let Some(suffix) = self.channel.suffix() {
+            write!(f, " {}", suffix)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const VERSIONS: &[&str] = &[
+        "86.0.616.0",
+        "104.0.1293.44",
+        "104.0.1293.44 beta",
+        "104.0.1293.47",
+        "104.0.1294.0 dev",
+        "105.0.1343.4 canary",
+        "105.0.1343.10",
+    ];
+
+    #[test]
+    fn parse_channels() {
+        let version: BrowserVersion = "105.0.1343.4 canary".parse().unwrap();
+        assert_eq!(
+            (
+                version.major(),
+                version.minor(),
+                version.build(),
+                version.patch()
+            ),
+            (105, 0, 1343, 4)
+        );
+        assert_eq!(version.channel(), BrowserChannel::Canary);
+        assert_eq!(
+            BrowserVersion::parse("104.0.1293.44 Beta")
+                .unwrap()
+                .channel(),
+            BrowserChannel::Beta
+        );
+        assert_eq!(
+            BrowserVersion::target_product_version().to_string(),
+            CORE_WEBVIEW_TARGET_PRODUCT_VERSION
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for version in [
+            "",
+            "104",
+            "104.0.1293",
+            "104.0.1293.44.1",
+            "104.0.1293.x",
+            "104.0.1293.44 nightly",
+            "104.0.1293.44 beta 2",
+        ] {
+            assert!(
+                matches!(
+                    BrowserVersion::parse(version),
+                    Err(Error::InvalidVersion(_))
+                ),
+                "{:?} should not parse",
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for version in VERSIONS {
+            assert_eq!(
+                &BrowserVersion::parse(version).unwrap().to_string(),
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn compare_ignores_channel() {
+        let stable = BrowserVersion::parse("104.0.1293.44").unwrap();
+        let beta = BrowserVersion::parse("104.0.1293.44 beta").unwrap();
+        assert_eq!(stable, beta);
+        assert_eq!(stable.cmp(&beta), Ordering::Equal);
+        assert!(BrowserVersion::parse("104.0.1293.47").unwrap() > beta);
+        assert!(
+            BrowserVersion::parse("105.0.1343.4 canary").unwrap()
+                < "105.0.1343.10".parse().unwrap()
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn compare_matches_ffi() {
+        use windows::core::HSTRING;
+
+        use webview2_com_sys::Microsoft::Web::WebView2::Win32::CompareBrowserVersions;
+
+        for left in VERSIONS {
+            for right in VERSIONS {
+                let mut result = 0;
+                unsafe {
+                    CompareBrowserVersions(
+                        &HSTRING::from(*left),
+                        &HSTRING::from(*right),
+                        &mut result,
+                    )
+                }
+                .unwrap();
+                let expected = result.cmp(&0);
+                let actual = BrowserVersion::parse(left)
+                    .unwrap()
+                    .cmp(&BrowserVersion::parse(right).unwrap());
+                assert_eq!(actual, expected, "{} vs. {}", left, right);
+            }
+        }
+    }
+}
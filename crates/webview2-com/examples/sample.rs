@@ -1,16 +1,19 @@
 #![windows_subsystem = "windows"]
 
+extern crate futures;
 extern crate serde;
 extern crate serde_json;
 extern crate webview2_com;
 extern crate windows;
 
 use std::{
+    cell::RefCell,
     collections::HashMap,
     fmt, mem, ptr,
     sync::{mpsc, Arc, Mutex},
 };
 
+use futures::StreamExt;
 use serde::Deserialize;
 use serde_json::{Number, Value};
 use windows::{
@@ -18,7 +21,7 @@ use windows::{
     Win32::{
         Foundation::{E_POINTER, HWND, LPARAM, LRESULT, RECT, SIZE, WPARAM},
         Graphics::Gdi,
-        System::{Com::*, LibraryLoader, Threading, WinRT::EventRegistrationToken},
+        System::{Com::*, LibraryLoader},
         UI::{
             HiDpi,
             Input::KeyboardAndMouse,
@@ -49,9 +52,9 @@ fn main() -> Result<()> {
             }
         }
 
-        Err(Error::WebView2Error(webview2_com::Error::CallbackError(
-            String::from(r#"Usage: window.hostCallback("Add", a, b)"#),
-        )))
+        Err(Error::WebView2Error(
+            webview2_com::CallbackError::new(r#"Usage: window.hostCallback("Add", a, b)"#).into(),
+        ))
     })?;
 
     // Configure the target URL and add an init script to trigger the calculator callback.
@@ -76,7 +79,10 @@ pub enum Error {
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Self::WebView2Error(err) => write!(f, "{}", err),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
 
@@ -172,8 +178,12 @@ impl FrameWindow {
 
 struct WebViewController(ICoreWebView2Controller);
 
-type WebViewSender = mpsc::Sender<Box<dyn FnOnce(WebView) + Send>>;
-type WebViewReceiver = mpsc::Receiver<Box<dyn FnOnce(WebView) + Send>>;
+thread_local! {
+    /// The `WebView` whose message loop is running on this thread, for jobs queued with
+    /// `WebView::dispatch`.
+    static DISPATCHED_WEBVIEW: RefCell<Option<WebView>> = const { RefCell::new(None) };
+}
+
 type BindingCallback = Box<dyn FnMut(Vec<Value>) -> Result<Value>>;
 type BindingsMap = HashMap<String, BindingCallback>;
 
@@ -181,13 +191,12 @@ type BindingsMap = HashMap<String, BindingCallback>;
 pub struct WebView {
     controller: Arc<WebViewController>,
     webview: Arc<ICoreWebView2>,
-    tx: WebViewSender,
-    rx: Arc<WebViewReceiver>,
-    thread_id: u32,
+    dispatcher: Dispatcher,
     bindings: Arc<Mutex<BindingsMap>>,
     frame: Option<FrameWindow>,
     parent: Arc<HWND>,
     url: Arc<Mutex<String>>,
+    message_received: Arc<Mutex<Option<EventRegistration>>>,
 }
 
 impl Drop for WebViewController {
@@ -219,7 +228,7 @@ impl WebView {
             CreateCoreWebView2EnvironmentCompletedHandler::wait_for_async_operation(
                 Box::new(|environmentcreatedhandler| unsafe {
                     CreateCoreWebView2Environment(&environmentcreatedhandler)
-                        .map_err(webview2_com::Error::WindowsError)
+                        .map_err(webview2_com::Error::from)
                 }),
                 Box::new(move |error_code, environment| {
                     error_code?;
@@ -233,26 +242,11 @@ impl WebView {
                 .map_err(|_| Error::WebView2Error(webview2_com::Error::SendError))?
         }?;
 
-        let controller = {
-            let (tx, rx) = mpsc::channel();
-
-            CreateCoreWebView2ControllerCompletedHandler::wait_for_async_operation(
-                Box::new(move |handler| unsafe {
-                    environment
-                        .CreateCoreWebView2Controller(parent, &handler)
-                        .map_err(webview2_com::Error::WindowsError)
-                }),
-                Box::new(move |error_code, controller| {
-                    error_code?;
-                    tx.send(controller.ok_or_else(|| windows::core::Error::from(E_POINTER)))
-                        .expect("send over mpsc channel");
-                    Ok(())
-                }),
-            )?;
-
-            rx.recv()
-                .map_err(|_| Error::WebView2Error(webview2_com::Error::SendError))?
-        }?;
+        let controller = webview2_com::create_controller(
+            &environment,
+            parent,
+            &webview2_com::ControllerOptions::new(),
+        )?;
 
         let size = get_window_size(parent);
         let mut client_rect = RECT::default();
@@ -281,60 +275,62 @@ impl WebView {
             *frame.size.lock()? = size;
         }
 
-        let (tx, rx) = mpsc::channel();
-        let rx = Arc::new(rx);
-        let thread_id = unsafe { Threading::GetCurrentThreadId() };
-
         let webview = WebView {
             controller: Arc::new(WebViewController(controller)),
             webview: Arc::new(webview),
-            tx,
-            rx,
-            thread_id,
+            dispatcher: Dispatcher::new(),
             bindings: Arc::new(Mutex::new(HashMap::new())),
             frame,
             parent: Arc::new(parent),
             url: Arc::new(Mutex::new(String::new())),
+            message_received: Arc::new(Mutex::new(None)),
         };
 
         // Inject the invoke handler.
         webview
             .init(r#"window.external = { invoke: s => window.chrome.webview.postMessage(s) };"#)?;
 
+        // The handler holds a clone of the WebView, so the registration is removed in
+        // `terminate` to release it.
         let bindings = webview.bindings.clone();
         let bound = webview.clone();
-        unsafe {
-            let mut _token = EventRegistrationToken::default();
-            webview.webview.add_WebMessageReceived(
-                &WebMessageReceivedEventHandler::create(Box::new(move |_webview, args| {
-                    if let Some(args) = args {
-                        let mut message = PWSTR(ptr::null_mut());
-                        if args.WebMessageAsJson(&mut message).is_ok() {
-                            let message = CoTaskMemPWSTR::from(message);
-                            if let Ok(value) =
-                                serde_json::from_str::<InvokeMessage>(&message.to_string())
-                            {
-                                if let Ok(mut bindings) = bindings.try_lock() {
-                                    if let Some(f) = bindings.get_mut(&value.method) {
-                                        match (*f)(value.params) {
-                                            Ok(result) => bound.resolve(value.id, 0, result),
-                                            Err(err) => bound.resolve(
-                                                value.id,
-                                                1,
-                                                Value::String(format!("{:#?}", err)),
-                                            ),
-                                        }
-                                        .unwrap();
+        let registrar = webview.webview.clone();
+        let remover = webview.webview.clone();
+        let registration = WebMessageReceivedEventHandler::register(
+            Box::new(move |_webview, args| {
+                if let Some(args) = args {
+                    let mut message = PWSTR(ptr::null_mut());
+                    if unsafe { args.WebMessageAsJson(&mut message) }.is_ok() {
+                        let message = CoTaskMemPWSTR::from(message);
+                        if let Ok(value) =
+                            serde_json::from_str::<InvokeMessage>(&message.to_string())
+                        {
+                            if let Ok(mut bindings) = bindings.try_lock() {
+                                if let Some(f) = bindings.get_mut(&value.method) {
+                                    match (*f)(value.params) {
+                                        Ok(result) => bound.resolve(value.id, 0, result),
+                                        Err(err) => bound.resolve(
+                                            value.id,
+                                            1,
+                                            Value::String(err.to_string()),
+                                        ),
                                     }
+                                    .unwrap();
                                 }
                             }
                         }
                     }
-                    Ok(())
-                })),
-                &mut _token,
-            )?;
-        }
+                }
+                Ok(())
+            }),
+            Box::new(move |handler, token| unsafe {
+                registrar
+                    .add_WebMessageReceived(&handler, token)
+                    .map_err(webview2_com::Error::from)
+            }),
+            Box::new(move |token| unsafe { remover.remove_WebMessageReceived(token) }),
+        )?;
+        *webview.message_received.lock()? = Some(registration);
 
         if webview.frame.is_some() {
             WebView::set_window_webview(parent, Some(Box::new(webview.clone())));
@@ -346,23 +342,25 @@ impl WebView {
     pub fn run(self) -> Result<()> {
         let webview = self.webview.as_ref();
         let url = self.url.try_lock()?.clone();
-        let (tx, rx) = mpsc::channel();
 
         if !url.is_empty() {
-            let handler =
-                NavigationCompletedEventHandler::create(Box::new(move |_sender, _args| {
-                    tx.send(()).expect("send over mpsc channel");
-                    Ok(())
-                }));
-            let mut token = EventRegistrationToken::default();
+            let registrar = webview.clone();
+            let (mut completed, token) =
+                NavigationCompletedEventHandler::stream(Box::new(move |handler, token| unsafe {
+                    registrar
+                        .add_NavigationCompleted(&handler, token)
+                        .map_err(webview2_com::Error::from)
+                }))?;
+            let remover = webview.clone();
+            let _registration = EventRegistration::new(
+                token,
+                Box::new(move |token| unsafe { remover.remove_NavigationCompleted(token) }),
+            );
             unsafe {
-                webview.add_NavigationCompleted(&handler, &mut token)?;
                 let url = CoTaskMemPWSTR::from(url.as_str());
                 webview.Navigate(*url.as_ref().as_pcwstr())?;
-                let result = webview2_com::wait_with_pump(rx);
-                webview.remove_NavigationCompleted(token)?;
-                result?;
             }
+            LocalExecutor::new().run_until(completed.next())?;
         }
 
         if let Some(frame) = self.frame.as_ref() {
@@ -376,11 +374,12 @@ impl WebView {
 
         let mut msg = MSG::default();
         let h_wnd = HWND::default();
+        DISPATCHED_WEBVIEW.with(|webview| *webview.borrow_mut() = Some(self.clone()));
 
-        loop {
-            while let Ok(f) = self.rx.try_recv() {
-                (f)(self.clone());
-            }
+        let result = loop {
+            // Nested message loops, e.g. in `wait_for_async_operation`, drop `WM_APP` without
+            // running the jobs, so check for them on every pass instead of waiting for it.
+            self.dispatcher.pump_dispatcher();
 
             unsafe {
                 let result = WindowsAndMessaging::GetMessageW(&mut msg, h_wnd, 0, 0).0;
@@ -397,7 +396,10 @@ impl WebView {
                     },
                 }
             }
-        }
+        };
+
+        DISPATCHED_WEBVIEW.with(|webview| webview.borrow_mut().take());
+        result
     }
 
     pub fn terminate(self) -> Result<()> {
@@ -405,6 +407,10 @@ impl WebView {
             WindowsAndMessaging::PostQuitMessage(0);
         })?;
 
+        if let Some(registration) = self.message_received.lock()?.take() {
+            registration.unregister()?;
+        }
+
         if self.frame.is_some() {
             WebView::set_window_webview(self.get_window(), None);
         }
@@ -470,7 +476,7 @@ impl WebView {
                 let js = CoTaskMemPWSTR::from(js.as_str());
                 webview
                     .AddScriptToExecuteOnDocumentCreated(*js.as_ref().as_pcwstr(), &handler)
-                    .map_err(webview2_com::Error::WindowsError)
+                    .map_err(webview2_com::Error::from)
             }),
             Box::new(|error_code, _id| error_code),
         )?;
@@ -485,27 +491,25 @@ impl WebView {
                 let js = CoTaskMemPWSTR::from(js.as_str());
                 webview
                     .ExecuteScript(*js.as_ref().as_pcwstr(), &handler)
-                    .map_err(webview2_com::Error::WindowsError)
+                    .map_err(webview2_com::Error::from)
             }),
             Box::new(|error_code, _result| error_code),
         )?;
         Ok(self)
     }
 
+    /// Run `f` on the UI thread the next time `run` checks for jobs. `WebView` is not `Send`, so
+    /// the job gets its own clone from the thread which is running the message loop.
     pub fn dispatch<F>(&self, f: F) -> Result<&Self>
     where
         F: FnOnce(WebView) + Send + 'static,
     {
-        self.tx.send(Box::new(f)).expect("send the fn");
-
-        unsafe {
-            WindowsAndMessaging::PostThreadMessageW(
-                self.thread_id,
-                WindowsAndMessaging::WM_APP,
-                WPARAM::default(),
-                LPARAM::default(),
-            );
-        }
+        self.dispatcher.dispatch(move || {
+            let webview = DISPATCHED_WEBVIEW.with(|webview| webview.borrow().clone());
+            if let Some(webview) = webview {
+                f(webview);
+            }
+        })?;
         Ok(self)
     }
 
@@ -1,3 +1,6 @@
+use std::{cell::RefCell, collections::VecDeque};
+
+use futures::channel::mpsc::UnboundedReceiver;
 use windows::{
     core::{IUnknown, Interface, HRESULT, PCWSTR},
     Win32::{Foundation::BOOL, System::Com::IStream},
@@ -5,7 +8,9 @@ use windows::{
 
 use windows_implement::implement;
 
-use crate::{pwstr::string_from_pcwstr, Microsoft::Web::WebView2::Win32::*};
+use webview2_com_util::string_from_pcwstr;
+
+use crate::Microsoft::Web::WebView2::Win32::*;
 
 pub trait ClosureArg {
     type Output: Sized;
@@ -83,427 +88,471 @@ pub type EventClosure<Arg1, Arg2> = Box<
     ) -> windows::core::Result<()>,
 >;
 
-#[event_callback]
+/// Storage for an [`EventClosure`] which is safe to [`invoke`](ReentrantEventClosure::invoke)
+/// reentrantly, e.g. when WebView2 raises the same event again from a nested message loop inside
+/// the closure. Instead of calling the closure while it is still running, nested calls are queued
+/// and replayed in order by the outermost call once the closure returns.
+pub struct ReentrantEventClosure<Arg1: ClosureArg, Arg2: ClosureArg> {
+    closure: RefCell<EventClosure<Arg1, Arg2>>,
+    deferred: RefCell<VecDeque<(Arg1::Output, Arg2::Output)>>,
+}
+
+impl<Arg1: ClosureArg, Arg2: ClosureArg> ReentrantEventClosure<Arg1, Arg2> {
+    pub fn new(closure: EventClosure<Arg1, Arg2>) -> Self {
+        Self {
+            closure: RefCell::new(closure),
+            deferred: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    /// Call the closure, or queue the arguments if it is already running further up the stack.
+    /// The outermost call returns the first error from any of the calls it made.
+    pub fn invoke(&self, arg_1: Arg1::Output, arg_2: Arg2::Output) -> windows::core::Result<()> {
+        let mut closure = match self.closure.try_borrow_mut() {
+            Ok(closure) => closure,
+            Err(_) => {
+                self.deferred.borrow_mut().push_back((arg_1, arg_2));
+                return Ok(());
+            }
+        };
+
+        let mut result = closure(arg_1, arg_2);
+
+        loop {
+            let next = self.deferred.borrow_mut().pop_front();
+            match next {
+                Some((arg_1, arg_2)) => result = result.and(closure(arg_1, arg_2)),
+                None => break result,
+            }
+        }
+    }
+}
+
+/// Generic stream of `(sender, args)` pairs for [`event_callback`].
+pub type EventStream<Arg1, Arg2> =
+    UnboundedReceiver<(<Arg1 as ClosureArg>::Output, <Arg2 as ClosureArg>::Output)>;
+
+#[callback]
 pub struct IsDefaultDownloadDialogOpenChangedEventHandler(
     ICoreWebView2IsDefaultDownloadDialogOpenChangedEventHandler,
     Option<ICoreWebView2>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct IsDocumentPlayingAudioChangedEventHandler(
     ICoreWebView2IsDocumentPlayingAudioChangedEventHandler,
     Option<ICoreWebView2>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct IsMutedChangedEventHandler(
     ICoreWebView2IsMutedChangedEventHandler,
     Option<ICoreWebView2>,
     Option<IUnknown>,
 );
 
-#[completed_callback]
+#[callback]
 pub struct CreateCoreWebView2EnvironmentCompletedHandler(
     ICoreWebView2CreateCoreWebView2EnvironmentCompletedHandler,
     HRESULT,
     Option<ICoreWebView2Environment>,
 );
 
-#[completed_callback]
+#[callback]
 pub struct CreateCoreWebView2ControllerCompletedHandler(
     ICoreWebView2CreateCoreWebView2ControllerCompletedHandler,
     HRESULT,
     Option<ICoreWebView2Controller>,
 );
 
-#[event_callback]
+#[callback]
 pub struct NewBrowserVersionAvailableEventHandler(
     ICoreWebView2NewBrowserVersionAvailableEventHandler,
     Option<ICoreWebView2Environment>,
     Option<IUnknown>,
 );
 
-#[completed_callback]
+#[callback]
 pub struct CreateCoreWebView2CompositionControllerCompletedHandler(
     ICoreWebView2CreateCoreWebView2CompositionControllerCompletedHandler,
     HRESULT,
     Option<ICoreWebView2CompositionController>,
 );
 
-#[event_callback]
+#[callback]
 pub struct CursorChangedEventHandler(
     ICoreWebView2CursorChangedEventHandler,
     Option<ICoreWebView2CompositionController>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct ZoomFactorChangedEventHandler(
     ICoreWebView2ZoomFactorChangedEventHandler,
     Option<ICoreWebView2Controller>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct MoveFocusRequestedEventHandler(
     ICoreWebView2MoveFocusRequestedEventHandler,
     Option<ICoreWebView2Controller>,
     Option<ICoreWebView2MoveFocusRequestedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct FocusChangedEventHandler(
     ICoreWebView2FocusChangedEventHandler,
     Option<ICoreWebView2Controller>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct AcceleratorKeyPressedEventHandler(
     ICoreWebView2AcceleratorKeyPressedEventHandler,
     Option<ICoreWebView2Controller>,
     Option<ICoreWebView2AcceleratorKeyPressedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct ProcessInfosChangedEventHandler(
     ICoreWebView2ProcessInfosChangedEventHandler,
     Option<ICoreWebView2Environment>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct RasterizationScaleChangedEventHandler(
     ICoreWebView2RasterizationScaleChangedEventHandler,
     Option<ICoreWebView2Controller>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct NavigationStartingEventHandler(
     ICoreWebView2NavigationStartingEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2NavigationStartingEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct ContentLoadingEventHandler(
     ICoreWebView2ContentLoadingEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2ContentLoadingEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct SourceChangedEventHandler(
     ICoreWebView2SourceChangedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2SourceChangedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct DOMContentLoadedEventHandler(
     ICoreWebView2DOMContentLoadedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2DOMContentLoadedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct HistoryChangedEventHandler(
     ICoreWebView2HistoryChangedEventHandler,
     Option<ICoreWebView2>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct NavigationCompletedEventHandler(
     ICoreWebView2NavigationCompletedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2NavigationCompletedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct ScriptDialogOpeningEventHandler(
     ICoreWebView2ScriptDialogOpeningEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2ScriptDialogOpeningEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct PermissionRequestedEventHandler(
     ICoreWebView2PermissionRequestedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2PermissionRequestedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct ProcessFailedEventHandler(
     ICoreWebView2ProcessFailedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2ProcessFailedEventArgs>,
 );
 
-#[completed_callback]
+#[callback]
 pub struct PrintToPdfCompletedHandler(ICoreWebView2PrintToPdfCompletedHandler, HRESULT, BOOL);
 
-#[completed_callback]
+#[callback]
 pub struct AddScriptToExecuteOnDocumentCreatedCompletedHandler(
     ICoreWebView2AddScriptToExecuteOnDocumentCreatedCompletedHandler,
     HRESULT,
     PCWSTR,
 );
 
-#[completed_callback]
+#[callback]
 pub struct ExecuteScriptCompletedHandler(
     ICoreWebView2ExecuteScriptCompletedHandler,
     HRESULT,
     PCWSTR,
 );
 
-#[completed_callback]
+#[callback]
 pub struct CapturePreviewCompletedHandler(ICoreWebView2CapturePreviewCompletedHandler, HRESULT);
 
-#[event_callback]
+#[callback]
 pub struct WebMessageReceivedEventHandler(
     ICoreWebView2WebMessageReceivedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2WebMessageReceivedEventArgs>,
 );
 
-#[completed_callback]
+#[callback]
 pub struct CallDevToolsProtocolMethodCompletedHandler(
     ICoreWebView2CallDevToolsProtocolMethodCompletedHandler,
     HRESULT,
     PCWSTR,
 );
 
-#[event_callback]
+#[callback]
 pub struct NewWindowRequestedEventHandler(
     ICoreWebView2NewWindowRequestedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2NewWindowRequestedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct DocumentTitleChangedEventHandler(
     ICoreWebView2DocumentTitleChangedEventHandler,
     Option<ICoreWebView2>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct ContainsFullScreenElementChangedEventHandler(
     ICoreWebView2ContainsFullScreenElementChangedEventHandler,
     Option<ICoreWebView2>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct WebResourceRequestedEventHandler(
     ICoreWebView2WebResourceRequestedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2WebResourceRequestedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct WebResourceResponseReceivedEventHandler(
     ICoreWebView2WebResourceResponseReceivedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2WebResourceResponseReceivedEventArgs>,
 );
 
-#[completed_callback]
+#[callback]
 pub struct WebResourceResponseViewGetContentCompletedHandler(
     ICoreWebView2WebResourceResponseViewGetContentCompletedHandler,
     HRESULT,
     Option<IStream>,
 );
 
-#[event_callback]
+#[callback]
 pub struct WindowCloseRequestedEventHandler(
     ICoreWebView2WindowCloseRequestedEventHandler,
     Option<ICoreWebView2>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct DownloadStartingEventHandler(
     ICoreWebView2DownloadStartingEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2DownloadStartingEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct BytesReceivedChangedEventHandler(
     ICoreWebView2BytesReceivedChangedEventHandler,
     Option<ICoreWebView2DownloadOperation>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct BrowserProcessExitedEventHandler(
     ICoreWebView2BrowserProcessExitedEventHandler,
     Option<ICoreWebView2Environment>,
     Option<ICoreWebView2BrowserProcessExitedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct EstimatedEndTimeChangedEventHandler(
     ICoreWebView2EstimatedEndTimeChangedEventHandler,
     Option<ICoreWebView2DownloadOperation>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct StateChangedEventHandler(
     ICoreWebView2StateChangedEventHandler,
     Option<ICoreWebView2DownloadOperation>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct DevToolsProtocolEventReceivedEventHandler(
     ICoreWebView2DevToolsProtocolEventReceivedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2DevToolsProtocolEventReceivedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct FrameContentLoadingEventHandler(
     ICoreWebView2FrameContentLoadingEventHandler,
     Option<ICoreWebView2Frame>,
     Option<ICoreWebView2ContentLoadingEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct FrameCreatedEventHandler(
     ICoreWebView2FrameCreatedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2FrameCreatedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct FrameDOMContentLoadedEventHandler(
     ICoreWebView2FrameDOMContentLoadedEventHandler,
     Option<ICoreWebView2Frame>,
     Option<ICoreWebView2DOMContentLoadedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct FrameDestroyedEventHandler(
     ICoreWebView2FrameDestroyedEventHandler,
     Option<ICoreWebView2Frame>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct FrameNameChangedEventHandler(
     ICoreWebView2FrameNameChangedEventHandler,
     Option<ICoreWebView2Frame>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct ClientCertificateRequestedEventHandler(
     ICoreWebView2ClientCertificateRequestedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2ClientCertificateRequestedEventArgs>,
 );
 
-#[completed_callback]
+#[callback]
 pub struct FrameNavigationCompletedEventHandler(
     ICoreWebView2FrameNavigationCompletedEventHandler,
     Option<ICoreWebView2Frame>,
     Option<ICoreWebView2NavigationCompletedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct FrameNavigationStartingEventHandler(
     ICoreWebView2FrameNavigationStartingEventHandler,
     Option<ICoreWebView2Frame>,
     Option<ICoreWebView2NavigationStartingEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct FrameWebMessageReceivedEventHandler(
     ICoreWebView2FrameWebMessageReceivedEventHandler,
     Option<ICoreWebView2Frame>,
     Option<ICoreWebView2WebMessageReceivedEventArgs>,
 );
 
-#[completed_callback]
+#[callback]
 pub struct GetCookiesCompletedHandler(
     ICoreWebView2GetCookiesCompletedHandler,
     HRESULT,
     Option<ICoreWebView2CookieList>,
 );
 
-#[completed_callback]
+#[callback]
 pub struct TrySuspendCompletedHandler(ICoreWebView2TrySuspendCompletedHandler, HRESULT, BOOL);
 
-#[event_callback]
+#[callback]
 pub struct BasicAuthenticationRequestedEventHandler(
     ICoreWebView2BasicAuthenticationRequestedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2BasicAuthenticationRequestedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct ContextMenuRequestedEventHandler(
     ICoreWebView2ContextMenuRequestedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2ContextMenuRequestedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct CustomItemSelectedEventHandler(
     ICoreWebView2CustomItemSelectedEventHandler,
     Option<ICoreWebView2ContextMenuItem>,
     Option<IUnknown>,
 );
 
-#[event_callback]
+#[callback]
 pub struct FramePermissionRequestedEventHandler(
     ICoreWebView2FramePermissionRequestedEventHandler,
     Option<ICoreWebView2Frame>,
     Option<ICoreWebView2PermissionRequestedEventArgs2>,
 );
 
-#[event_callback]
+#[callback]
 pub struct StatusBarTextChangedEventHandler(
     ICoreWebView2StatusBarTextChangedEventHandler,
     Option<ICoreWebView2>,
     Option<IUnknown>,
 );
 
-#[completed_callback]
+#[callback]
 pub struct ClearBrowsingDataCompletedHandler(
     ICoreWebView2ClearBrowsingDataCompletedHandler,
     HRESULT,
 );
 
-#[completed_callback]
+#[callback]
 pub struct ClearServerCertificateErrorActionsCompletedHandler(
     ICoreWebView2ClearServerCertificateErrorActionsCompletedHandler,
     HRESULT,
 );
 
-#[event_callback]
+#[callback]
 pub struct ServerCertificateErrorDetectedEventHandler(
     ICoreWebView2ServerCertificateErrorDetectedEventHandler,
     Option<ICoreWebView2>,
     Option<ICoreWebView2ServerCertificateErrorDetectedEventArgs>,
 );
 
-#[event_callback]
+#[callback]
 pub struct FaviconChangedEventHandler(
     ICoreWebView2FaviconChangedEventHandler,
     Option<ICoreWebView2>,
     Option<IUnknown>,
 );
 
-#[completed_callback]
+#[callback]
 pub struct GetFaviconCompletedHandler(
     ICoreWebView2GetFaviconCompletedHandler,
     HRESULT,
@@ -512,12 +561,28 @@ pub struct GetFaviconCompletedHandler(
 
 #[cfg(test)]
 mod test {
-    use std::{collections::BTreeSet, env, fs::File, io::Read, path::PathBuf};
-
+    use std::{
+        cell::Cell,
+        collections::BTreeSet,
+        env,
+        fs::File,
+        io::Read,
+        path::PathBuf,
+        rc::Rc,
+        time::{Duration, Instant},
+    };
+
+    use futures::{executor::block_on, StreamExt};
     use regex::Regex;
+    use windows::{
+        w,
+        Win32::Foundation::{E_UNEXPECTED, S_OK},
+    };
 
     use webview2_com_sys::callback_interfaces;
 
+    use super::*;
+
     #[test]
     fn all_implemented() {
         let mut source_path = PathBuf::from(
@@ -543,4 +608,191 @@ mod test {
             "all declared interfaces should be implemented"
         );
     }
+
+    #[test]
+    fn future_completed() {
+        let result = block_on(ExecuteScriptCompletedHandler::future(Box::new(
+            |handler| unsafe { handler.Invoke(S_OK, w!("42")).map_err(crate::Error::from) },
+        )))
+        .unwrap();
+        assert!(result.0.is_ok());
+        assert_eq!(&result.1, "42");
+    }
+
+    #[test]
+    fn future_canceled() {
+        let result = block_on(CapturePreviewCompletedHandler::future(Box::new(
+            |_handler| Ok(()),
+        )));
+        assert!(matches!(result, Err(crate::Error::TaskCanceled)));
+    }
+
+    #[test]
+    fn stream_events() {
+        let (stream, token) =
+            NavigationCompletedEventHandler::stream(Box::new(|handler, token| unsafe {
+                token.value = 1;
+                handler.Invoke(None, None).map_err(crate::Error::from)?;
+                handler.Invoke(None, None).map_err(crate::Error::from)
+            }))
+            .unwrap();
+        assert_eq!(token.value, 1);
+        let events: Vec<_> = block_on(stream.take(2).collect());
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|(sender, args)| sender.is_none() && args.is_none()));
+    }
+
+    #[test]
+    fn register_and_remove() {
+        let removed = Rc::new(Cell::new(None));
+        let tracker = removed.clone();
+        let registration = DocumentTitleChangedEventHandler::register(
+            Box::new(|_sender, _args| Ok(())),
+            Box::new(|_handler, token| {
+                token.value = 4;
+                Ok(())
+            }),
+            Box::new(move |token| {
+                tracker.set(Some(token.value));
+                Ok(())
+            }),
+        )
+        .unwrap();
+        assert_eq!(registration.token().value, 4);
+        assert_eq!(removed.get(), None);
+        drop(registration);
+        assert_eq!(removed.get(), Some(4));
+    }
+
+    #[test]
+    fn wait_for_async_operation_deadline() {
+        let pending: Rc<RefCell<Option<ICoreWebView2CapturePreviewCompletedHandler>>> =
+            Default::default();
+        let slot = pending.clone();
+        let result = CapturePreviewCompletedHandler::wait_for_async_operation_until(
+            Box::new(move |handler| {
+                *slot.borrow_mut() = Some(handler);
+                Ok(())
+            }),
+            Box::new(|error_code| error_code),
+            Some(Instant::now() + Duration::from_millis(10)),
+        );
+        assert!(matches!(result, Err(crate::Error::Timeout)));
+
+        // A late completion after the receiver is dropped should be ignored, not panic.
+        let _policy = crate::ScopedPanicPolicy::new(crate::PanicPolicy::Propagate);
+        let handler = pending
+            .borrow_mut()
+            .take()
+            .expect("handler should be stored");
+        assert!(unsafe { handler.Invoke(S_OK) }.is_ok());
+        assert!(crate::take_callback_panic().is_none());
+    }
+
+    #[test]
+    fn wait_for_async_operation_dropped() {
+        let _policy = crate::ScopedPanicPolicy::new(crate::PanicPolicy::Log);
+        let result = CapturePreviewCompletedHandler::wait_for_async_operation(
+            Box::new(|handler| {
+                let result = unsafe { handler.Invoke(S_OK) };
+                assert_eq!(result.unwrap_err().code(), E_UNEXPECTED);
+                Ok(())
+            }),
+            Box::new(|_error_code| panic!("completed panic")),
+        );
+        assert!(matches!(result, Err(crate::Error::CallbackError(_))));
+    }
+
+    #[test]
+    fn panic_in_callback() {
+        let policy = crate::ScopedPanicPolicy::new(crate::PanicPolicy::Propagate);
+        let handler = DocumentTitleChangedEventHandler::create(Box::new(|_sender, _args| {
+            panic!("callback panic");
+        }));
+        let result = unsafe { handler.Invoke(None, None) };
+        assert_eq!(result.unwrap_err().code(), E_UNEXPECTED);
+        let payload = crate::take_callback_panic().expect("should store the panic");
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"callback panic"));
+
+        policy.set(crate::PanicPolicy::Log);
+        let result = unsafe { handler.Invoke(None, None) };
+        assert_eq!(result.unwrap_err().code(), E_UNEXPECTED);
+        assert!(crate::take_callback_panic().is_none());
+    }
+
+    #[test]
+    fn reentrant_event() {
+        let slot: Rc<RefCell<Option<ICoreWebView2DocumentTitleChangedEventHandler>>> =
+            Default::default();
+        let depth = Rc::new(Cell::new(0));
+        let calls = Rc::new(RefCell::new(Vec::new()));
+
+        let handler = {
+            let slot = slot.clone();
+            let depth = depth.clone();
+            let calls = calls.clone();
+            DocumentTitleChangedEventHandler::create(Box::new(move |_sender, _args| {
+                depth.set(depth.get() + 1);
+                let call = calls.borrow().len() + 1;
+                calls.borrow_mut().push((call, depth.get()));
+                if call == 1 {
+                    let handler = slot.borrow().clone().expect("handler is set");
+                    unsafe { handler.Invoke(None, None) }?;
+                    unsafe { handler.Invoke(None, None) }?;
+                    assert_eq!(calls.borrow().len(), 1, "nested calls should be deferred");
+                }
+                depth.set(depth.get() - 1);
+                Ok(())
+            }))
+        };
+        *slot.borrow_mut() = Some(handler.clone());
+
+        unsafe { handler.Invoke(None, None) }.unwrap();
+        assert_eq!(*calls.borrow(), vec![(1, 1), (2, 1), (3, 1)]);
+
+        unsafe { handler.Invoke(None, None) }.unwrap();
+        assert_eq!(calls.borrow().len(), 4);
+        slot.borrow_mut().take();
+    }
+
+    #[test]
+    fn reentrant_completed() {
+        let slot: Rc<RefCell<Option<ICoreWebView2ExecuteScriptCompletedHandler>>> =
+            Default::default();
+        let calls = Rc::new(Cell::new(0));
+
+        let handler = {
+            let slot = slot.clone();
+            let calls = calls.clone();
+            ExecuteScriptCompletedHandler::create(Box::new(move |_error_code, _result| {
+                calls.set(calls.get() + 1);
+                let handler = slot.borrow().clone().expect("handler is set");
+                unsafe { handler.Invoke(S_OK, w!("nested")) }
+            }))
+        };
+        *slot.borrow_mut() = Some(handler.clone());
+
+        unsafe { handler.Invoke(S_OK, w!("outer")) }.unwrap();
+        unsafe { handler.Invoke(S_OK, w!("again")) }.unwrap();
+        assert_eq!(calls.get(), 1);
+        slot.borrow_mut().take();
+    }
+
+    #[test]
+    fn frame_navigation_completed_repeats() {
+        let calls = Rc::new(Cell::new(0));
+        let handler = {
+            let calls = calls.clone();
+            FrameNavigationCompletedEventHandler::create(Box::new(move |_frame, _args| {
+                calls.set(calls.get() + 1);
+                Ok(())
+            }))
+        };
+
+        unsafe { handler.Invoke(None, None) }.unwrap();
+        unsafe { handler.Invoke(None, None) }.unwrap();
+        assert_eq!(calls.get(), 2);
+    }
 }
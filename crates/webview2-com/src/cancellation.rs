@@ -0,0 +1,48 @@
+use std::{ptr, sync::Arc};
+
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{CloseHandle, HANDLE, WAIT_OBJECT_0},
+        System::Threading,
+    },
+};
+
+struct EventHandle(HANDLE);
+
+impl Drop for EventHandle {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+/// Manual-reset Win32 event which can be signaled from any thread to cancel
+/// [`crate::wait_with_pump_cancellable`]. Clones share the same event.
+#[derive(Clone)]
+pub struct CancellationToken(Arc<EventHandle>);
+
+impl CancellationToken {
+    /// Create a new token which has not been canceled yet.
+    pub fn new() -> crate::Result<Self> {
+        let event = unsafe { Threading::CreateEventW(ptr::null(), true, false, PCWSTR::null()) }?;
+        Ok(Self(Arc::new(EventHandle(event))))
+    }
+
+    /// Signal the event. Once a token is canceled it stays canceled.
+    pub fn cancel(&self) {
+        unsafe {
+            Threading::SetEvent(self.0 .0);
+        }
+    }
+
+    /// Check the event without waiting for it.
+    pub fn is_canceled(&self) -> bool {
+        unsafe { Threading::WaitForSingleObject(self.0 .0, 0) == WAIT_OBJECT_0.0 }
+    }
+
+    pub(crate) fn handle(&self) -> HANDLE {
+        self.0 .0
+    }
+}
@@ -0,0 +1,494 @@
+use std::{iter::FusedIterator, mem};
+
+use windows::{
+    core::{Interface, Result, PWSTR},
+    Win32::Foundation::{BOOL, E_INVALIDARG, E_POINTER},
+};
+
+use windows_implement::implement;
+
+use crate::{
+    pwstr::{out_pwstr_from_str, take_pwstr},
+    Microsoft::Web::WebView2::Win32::*,
+};
+
+/// Implementation of [`ICoreWebView2StringCollection`] over a `Vec<String>`, for the APIs and
+/// tests which need to pass a string collection to WebView2 rather than read one from it.
+#[implement(ICoreWebView2StringCollection)]
+pub struct StringCollection(Vec<String>);
+
+impl StringCollection {
+    pub fn new(values: Vec<String>) -> Self {
+        Self(values)
+    }
+}
+
+impl From<Vec<String>> for StringCollection {
+    fn from(values: Vec<String>) -> Self {
+        Self::new(values)
+    }
+}
+
+#[allow(non_snake_case)]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+impl ICoreWebView2StringCollection_Impl for StringCollection {
+    fn Count(&self, value: *mut u32) -> Result<()> {
+        if value.is_null() {
+            E_POINTER.ok()
+        } else {
+            unsafe { *value = self.0.len() as u32 };
+            Ok(())
+        }
+    }
+
+    fn GetValueAtIndex(&self, index: u32, value: *mut PWSTR) -> Result<()> {
+        if value.is_null() {
+            E_POINTER.ok()
+        } else {
+            let item = self.0.get(index as usize).ok_or(E_INVALIDARG)?;
+            unsafe { *value = out_pwstr_from_str(item)? };
+            Ok(())
+        }
+    }
+}
+
+/// Conversions for an [`ICoreWebView2StringCollection`], whether it came from the runtime or
+/// from a [`StringCollection`].
+pub trait StringCollectionExt {
+    /// Copy every string in the collection to a `Vec<String>`.
+    fn to_vec(&self) -> crate::Result<Vec<String>>;
+}
+
+impl StringCollectionExt for ICoreWebView2StringCollection {
+    fn to_vec(&self) -> crate::Result<Vec<String>> {
+        self.iter().collect()
+    }
+}
+
+/// Collection interfaces which have a `Count` and `GetValueAtIndex` method.
+pub trait IndexedCollection: Interface + Clone {
+    type Item;
+
+    fn count(&self) -> Result<u32>;
+
+    fn value_at(&self, index: u32) -> Result<Self::Item>;
+
+    /// Iterate over a clone of the collection. `Count` is only called once, so the iterator
+    /// does not see any items which are inserted or removed after it is created.
+    fn iter(&self) -> IndexedIter<Self> {
+        IndexedIter::new(self.clone())
+    }
+}
+
+macro_rules! impl_indexed_collection {
+    ($($collection:ty => $item:ty),* $(,)?) => {
+        $(
+            impl IndexedCollection for $collection {
+                type Item = $item;
+
+                fn count(&self) -> Result<u32> {
+                    let mut count = 0;
+                    unsafe { self.Count(&mut count) }?;
+                    Ok(count)
+                }
+
+                fn value_at(&self, index: u32) -> Result<$item> {
+                    unsafe { self.GetValueAtIndex(index) }
+                }
+            }
+        )*
+    };
+}
+
+impl_indexed_collection!(
+    ICoreWebView2ClientCertificateCollection => ICoreWebView2ClientCertificate,
+    ICoreWebView2ContextMenuItemCollection => ICoreWebView2ContextMenuItem,
+    ICoreWebView2CookieList => ICoreWebView2Cookie,
+    ICoreWebView2ProcessInfoCollection => ICoreWebView2ProcessInfo,
+);
+
+impl IndexedCollection for ICoreWebView2StringCollection {
+    type Item = String;
+
+    fn count(&self) -> Result<u32> {
+        let mut count = 0;
+        unsafe { self.Count(&mut count) }?;
+        Ok(count)
+    }
+
+    fn value_at(&self, index: u32) -> Result<String> {
+        let mut value = PWSTR::null();
+        unsafe { self.GetValueAtIndex(index, &mut value) }?;
+        Ok(take_pwstr(value))
+    }
+}
+
+/// [`Iterator`] over an [`IndexedCollection`]. If `Count` fails, the iterator yields that error
+/// and then ends. If `GetValueAtIndex` fails, it yields the error and moves on to the next index.
+pub struct IndexedIter<C: IndexedCollection> {
+    collection: C,
+    index: u32,
+    count: u32,
+    error: Option<windows::core::Error>,
+}
+
+impl<C: IndexedCollection> IndexedIter<C> {
+    pub fn new(collection: C) -> Self {
+        let (count, error) = match collection.count() {
+            Ok(count) => (count, None),
+            Err(err) => (0, Some(err)),
+        };
+
+        Self {
+            collection,
+            index: 0,
+            count,
+            error,
+        }
+    }
+}
+
+impl<C: IndexedCollection> Iterator for IndexedIter<C> {
+    type Item = crate::Result<C::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err.into()));
+        }
+
+        if self.index < self.count {
+            let index = self.index;
+            self.index += 1;
+            Some(self.collection.value_at(index).map_err(crate::Error::from))
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.count - self.index) as usize + usize::from(self.error.is_some());
+        (len, Some(len))
+    }
+}
+
+impl<C: IndexedCollection> ExactSizeIterator for IndexedIter<C> {}
+
+impl<C: IndexedCollection> FusedIterator for IndexedIter<C> {}
+
+/// Iterator interfaces which have a `HasCurrent`, `GetCurrent`, and `MoveNext` method.
+pub trait CursorCollection: Interface + Clone {
+    type Item;
+
+    fn has_current(&self) -> Result<bool>;
+
+    fn current(&self) -> Result<Self::Item>;
+
+    fn move_next(&self) -> Result<bool>;
+
+    /// Iterate over the remaining items, starting with the current one. The cursor is shared
+    /// with any other clones of the interface, so it is only safe to iterate over it once.
+    fn iter(&self) -> CursorIter<Self> {
+        CursorIter::new(self.clone())
+    }
+}
+
+impl CursorCollection for ICoreWebView2FrameInfoCollectionIterator {
+    type Item = ICoreWebView2FrameInfo;
+
+    fn has_current(&self) -> Result<bool> {
+        let mut has_current = BOOL::default();
+        unsafe { self.HasCurrent(&mut has_current) }?;
+        Ok(has_current.as_bool())
+    }
+
+    fn current(&self) -> Result<ICoreWebView2FrameInfo> {
+        unsafe { self.GetCurrent() }
+    }
+
+    fn move_next(&self) -> Result<bool> {
+        let mut has_next = BOOL::default();
+        unsafe { self.MoveNext(&mut has_next) }?;
+        Ok(has_next.as_bool())
+    }
+}
+
+impl CursorCollection for ICoreWebView2HttpHeadersCollectionIterator {
+    /// Header name and value.
+    type Item = (String, String);
+
+    fn has_current(&self) -> Result<bool> {
+        let mut has_current = BOOL::default();
+        unsafe { self.HasCurrentHeader(&mut has_current) }?;
+        Ok(has_current.as_bool())
+    }
+
+    fn current(&self) -> Result<(String, String)> {
+        let mut name = PWSTR::null();
+        let mut value = PWSTR::null();
+        unsafe { self.GetCurrentHeader(&mut name, &mut value) }?;
+        Ok((take_pwstr(name), take_pwstr(value)))
+    }
+
+    fn move_next(&self) -> Result<bool> {
+        let mut has_next = BOOL::default();
+        unsafe { self.MoveNext(&mut has_next) }?;
+        Ok(has_next.as_bool())
+    }
+}
+
+enum CursorState<C> {
+    Start(C),
+    Moving(C),
+    Failed(windows::core::Error),
+    Done,
+}
+
+/// [`Iterator`] over a [`CursorCollection`]. The cursor has no count, so this is not an
+/// [`ExactSizeIterator`]. The first error is yielded as an item, and then the iterator ends.
+pub struct CursorIter<C: CursorCollection>(CursorState<C>);
+
+impl<C: CursorCollection> CursorIter<C> {
+    pub fn new(cursor: C) -> Self {
+        Self(CursorState::Start(cursor))
+    }
+
+    fn failed(err: windows::core::Error) -> Self {
+        Self(CursorState::Failed(err))
+    }
+}
+
+impl<C: CursorCollection> Iterator for CursorIter<C> {
+    type Item = crate::Result<C::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (cursor, has_current) = match mem::replace(&mut self.0, CursorState::Done) {
+            CursorState::Start(cursor) => {
+                let has_current = cursor.has_current();
+                (cursor, has_current)
+            }
+            CursorState::Moving(cursor) => {
+                let has_next = cursor.move_next();
+                (cursor, has_next)
+            }
+            CursorState::Failed(err) => return Some(Err(err.into())),
+            CursorState::Done => return None,
+        };
+
+        match has_current {
+            Ok(true) => match cursor.current() {
+                Ok(item) => {
+                    self.0 = CursorState::Moving(cursor);
+                    Some(Ok(item))
+                }
+                Err(err) => Some(Err(err.into())),
+            },
+            Ok(false) => None,
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+impl<C: CursorCollection> FusedIterator for CursorIter<C> {}
+
+/// Iterate over an [`ICoreWebView2FrameInfoCollection`] through its
+/// [`ICoreWebView2FrameInfoCollectionIterator`].
+pub trait FrameInfoCollectionExt {
+    /// Get a new cursor from the collection and iterate over it. If `GetIterator` fails, the
+    /// iterator yields that error and then ends.
+    fn iter(&self) -> CursorIter<ICoreWebView2FrameInfoCollectionIterator>;
+}
+
+impl FrameInfoCollectionExt for ICoreWebView2FrameInfoCollection {
+    fn iter(&self) -> CursorIter<ICoreWebView2FrameInfoCollectionIterator> {
+        match unsafe { self.GetIterator() } {
+            Ok(cursor) => CursorIter::new(cursor),
+            Err(err) => CursorIter::failed(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::Cell, ptr};
+
+    use windows::Win32::Foundation::E_FAIL;
+
+    use super::*;
+
+    fn test_collection() -> ICoreWebView2StringCollection {
+        StringCollection::new(vec![
+            "Fake Issuer".to_string(),
+            String::new(),
+            "Fake \u{1F600} Issuer".to_string(),
+        ])
+        .into()
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(
+            test_collection().to_vec().unwrap(),
+            vec![
+                "Fake Issuer".to_string(),
+                String::new(),
+                "Fake \u{1F600} Issuer".to_string(),
+            ]
+        );
+
+        let empty: ICoreWebView2StringCollection = StringCollection::new(Vec::new()).into();
+        assert!(empty.to_vec().unwrap().is_empty());
+    }
+
+    #[test]
+    fn vtable_errors() {
+        let collection = test_collection();
+        let mut value = PWSTR::null();
+        let result = unsafe { collection.GetValueAtIndex(3, &mut value) };
+        assert_eq!(result.unwrap_err().code(), E_INVALIDARG);
+        assert!(value.is_null());
+
+        let result = unsafe { collection.Count(ptr::null_mut()) };
+        assert_eq!(result.unwrap_err().code(), E_POINTER);
+        let result = unsafe { collection.GetValueAtIndex(0, ptr::null_mut()) };
+        assert_eq!(result.unwrap_err().code(), E_POINTER);
+    }
+
+    #[test]
+    fn indexed_iter() {
+        let mut iter = test_collection().iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(&iter.next().unwrap().unwrap(), "Fake Issuer");
+        assert_eq!(iter.len(), 2);
+        assert_eq!(iter.collect::<crate::Result<Vec<_>>>().unwrap().len(), 2);
+    }
+
+    #[implement(ICoreWebView2StringCollection)]
+    struct FailingCollection;
+
+    #[allow(non_snake_case)]
+    impl ICoreWebView2StringCollection_Impl for FailingCollection {
+        fn Count(&self, _value: *mut u32) -> Result<()> {
+            E_FAIL.ok()
+        }
+
+        fn GetValueAtIndex(&self, _index: u32, _value: *mut PWSTR) -> Result<()> {
+            E_FAIL.ok()
+        }
+    }
+
+    #[test]
+    fn indexed_count_error() {
+        let collection: ICoreWebView2StringCollection = FailingCollection.into();
+        let mut iter = collection.iter();
+        assert_eq!(iter.len(), 1);
+        assert!(
+            matches!(iter.next(), Some(Err(crate::Error::WindowsError(err))) if err.code() == E_FAIL)
+        );
+        assert_eq!(iter.len(), 0);
+        assert!(iter.next().is_none());
+        assert!(collection.to_vec().is_err());
+    }
+
+    /// Cursor over a list of headers, which fails to get the header at `fail_at`.
+    #[implement(ICoreWebView2HttpHeadersCollectionIterator)]
+    struct TestHeaders {
+        headers: Vec<(&'static str, &'static str)>,
+        position: Cell<usize>,
+        fail_at: Option<usize>,
+    }
+
+    impl TestHeaders {
+        fn create(
+            headers: Vec<(&'static str, &'static str)>,
+            fail_at: Option<usize>,
+        ) -> ICoreWebView2HttpHeadersCollectionIterator {
+            Self {
+                headers,
+                position: Cell::new(0),
+                fail_at,
+            }
+            .into()
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    impl ICoreWebView2HttpHeadersCollectionIterator_Impl for TestHeaders {
+        fn GetCurrentHeader(&self, name: *mut PWSTR, value: *mut PWSTR) -> Result<()> {
+            let position = self.position.get();
+            if self.fail_at == Some(position) {
+                return E_FAIL.ok();
+            }
+            let (header_name, header_value) = self.headers.get(position).ok_or(E_INVALIDARG)?;
+            unsafe {
+                *name = out_pwstr_from_str(header_name)?;
+                *value = out_pwstr_from_str(header_value)?;
+            }
+            Ok(())
+        }
+
+        fn HasCurrentHeader(&self, has_current: *mut BOOL) -> Result<()> {
+            unsafe { *has_current = (self.position.get() < self.headers.len()).into() };
+            Ok(())
+        }
+
+        fn MoveNext(&self, has_next: *mut BOOL) -> Result<()> {
+            self.position.set(self.position.get() + 1);
+            self.HasCurrentHeader(has_next)
+        }
+    }
+
+    #[test]
+    fn cursor_iter() {
+        let headers = TestHeaders::create(
+            vec![("Content-Type", "text/html"), ("Cache-Control", "no-cache")],
+            None,
+        );
+        assert_eq!(
+            headers.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
+            vec![
+                ("Content-Type".to_string(), "text/html".to_string()),
+                ("Cache-Control".to_string(), "no-cache".to_string()),
+            ]
+        );
+
+        let empty = TestHeaders::create(Vec::new(), None);
+        assert!(empty.iter().next().is_none());
+    }
+
+    #[test]
+    fn cursor_error_ends_iter() {
+        let headers = TestHeaders::create(
+            vec![
+                ("Content-Type", "text/html"),
+                ("Cache-Control", "no-cache"),
+                ("Expires", "0"),
+            ],
+            Some(1),
+        );
+        let mut iter = headers.iter();
+        assert!(matches!(iter.next(), Some(Ok(_))));
+        assert!(
+            matches!(iter.next(), Some(Err(crate::Error::WindowsError(err))) if err.code() == E_FAIL)
+        );
+        assert!(iter.next().is_none());
+    }
+
+    #[implement(ICoreWebView2FrameInfoCollection)]
+    struct FailingFrames;
+
+    #[allow(non_snake_case)]
+    impl ICoreWebView2FrameInfoCollection_Impl for FailingFrames {
+        fn GetIterator(&self) -> Result<ICoreWebView2FrameInfoCollectionIterator> {
+            Err(E_FAIL.into())
+        }
+    }
+
+    #[test]
+    fn frame_info_iterator_error() {
+        let frames: ICoreWebView2FrameInfoCollection = FailingFrames.into();
+        let mut iter = frames.iter();
+        assert!(matches!(iter.next(), Some(Err(_))));
+        assert!(iter.next().is_none());
+    }
+}
@@ -0,0 +1,297 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc, Mutex,
+};
+
+use windows::{
+    core::{Interface, Result, HSTRING, PCWSTR, PWSTR},
+    Win32::Foundation::{BOOL, E_POINTER, HWND},
+};
+
+use windows_implement::implement;
+
+use crate::{
+    options::{get_bool, get_string, lock_string, set_bool, set_string},
+    CreateCoreWebView2ControllerCompletedHandler,
+    Microsoft::Web::WebView2::Win32::{
+        ICoreWebView2Controller, ICoreWebView2ControllerOptions,
+        ICoreWebView2ControllerOptions_Impl,
+        ICoreWebView2CreateCoreWebView2ControllerCompletedHandler, ICoreWebView2Environment,
+        ICoreWebView2Environment10,
+    },
+};
+
+/// Implementation of [`ICoreWebView2ControllerOptions`]. Like
+/// [`crate::CoreWebView2EnvironmentOptions`], it is `Send + Sync`.
+#[implement(ICoreWebView2ControllerOptions)]
+pub struct CoreWebView2ControllerOptions {
+    profile_name: Mutex<String>,
+    in_private_mode_enabled: AtomicBool,
+}
+
+impl Default for CoreWebView2ControllerOptions {
+    fn default() -> Self {
+        Self {
+            profile_name: Mutex::new(String::new()),
+            in_private_mode_enabled: AtomicBool::new(false),
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+impl ICoreWebView2ControllerOptions_Impl for CoreWebView2ControllerOptions {
+    fn ProfileName(&self, result: *mut PWSTR) -> Result<()> {
+        get_string(&self.profile_name, result)
+    }
+
+    fn SetProfileName(&self, value: &PCWSTR) -> Result<()> {
+        set_string(&self.profile_name, value)
+    }
+
+    fn IsInPrivateModeEnabled(&self, result: *mut BOOL) -> Result<()> {
+        get_bool(&self.in_private_mode_enabled, result)
+    }
+
+    fn SetIsInPrivateModeEnabled(&self, value: BOOL) -> Result<()> {
+        set_bool(&self.in_private_mode_enabled, value)
+    }
+}
+
+/// Typed builder for the options which are passed to
+/// `ICoreWebView2Environment10::CreateCoreWebView2ControllerWithOptions`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct ControllerOptions {
+    profile_name: Option<String>,
+    in_private_mode_enabled: bool,
+}
+
+impl ControllerOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Name of the profile to use, which is created in the user data folder if it does not exist.
+    pub fn profile_name<T: Into<String>>(mut self, value: T) -> Self {
+        self.profile_name = Some(value.into());
+        self
+    }
+
+    pub fn in_private_mode_enabled(mut self, value: bool) -> Self {
+        self.in_private_mode_enabled = value;
+        self
+    }
+
+    /// Create a [`CoreWebView2ControllerOptions`] with these values.
+    pub fn build(&self) -> ICoreWebView2ControllerOptions {
+        let options = CoreWebView2ControllerOptions::default();
+        if let Some(profile_name) = &self.profile_name {
+            *lock_string(&options.profile_name) = profile_name.clone();
+        }
+        options
+            .in_private_mode_enabled
+            .store(self.in_private_mode_enabled, Ordering::Release);
+        options.into()
+    }
+
+    /// Copy these values to an existing [`ICoreWebView2ControllerOptions`], e.g. one which was
+    /// returned by `ICoreWebView2Environment10::CreateCoreWebView2ControllerOptions`.
+    pub fn apply(&self, options: &ICoreWebView2ControllerOptions) -> crate::Result<()> {
+        unsafe {
+            if let Some(profile_name) = &self.profile_name {
+                options.SetProfileName(&HSTRING::from(profile_name.as_str()))?;
+            }
+            options.SetIsInPrivateModeEnabled(self.in_private_mode_enabled)?;
+        }
+        Ok(())
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Create a controller in the `parent` window, and pump messages until it is ready.
+///
+/// If `environment` supports [`ICoreWebView2Environment10`], the `options` are applied to the
+/// controller options which it creates, and passed to `CreateCoreWebView2ControllerWithOptions`.
+/// Older versions of the runtime fall back to `CreateCoreWebView2Controller`, as long as the
+/// `options` are all defaults. Otherwise this returns the `E_NOINTERFACE` error, rather than
+/// quietly creating a controller without the requested profile or InPrivate mode.
+pub fn create_controller(
+    environment: &ICoreWebView2Environment,
+    parent: HWND,
+    options: &ControllerOptions,
+) -> crate::Result<ICoreWebView2Controller> {
+    type CreateClosure = Box<
+        dyn FnOnce(ICoreWebView2CreateCoreWebView2ControllerCompletedHandler) -> crate::Result<()>,
+    >;
+
+    let create: CreateClosure = match environment.cast::<ICoreWebView2Environment10>() {
+        Ok(environment) => {
+            let controller_options = unsafe { environment.CreateCoreWebView2ControllerOptions() }?;
+            options.apply(&controller_options)?;
+            Box::new(move |handler| unsafe {
+                environment
+                    .CreateCoreWebView2ControllerWithOptions(parent, &controller_options, &handler)
+                    .map_err(crate::Error::from)
+            })
+        }
+        Err(_) if options.is_default() => {
+            let environment = environment.clone();
+            Box::new(move |handler| unsafe {
+                environment
+                    .CreateCoreWebView2Controller(parent, &handler)
+                    .map_err(crate::Error::from)
+            })
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let (tx, rx) = mpsc::channel();
+    CreateCoreWebView2ControllerCompletedHandler::wait_for_async_operation(
+        create,
+        Box::new(move |error_code, controller| {
+            error_code?;
+            // If the wait was canceled, nobody is left to receive a late result.
+            let _ = tx.send(controller.ok_or_else(|| windows::core::Error::from(E_POINTER)));
+            Ok(())
+        }),
+    )
+    .map_err(crate::Error::controller_creation_error)?;
+
+    rx.recv()
+        .map_err(|_| crate::Error::SendError)?
+        .map_err(crate::Error::from)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::Cell, ptr, rc::Rc};
+
+    use windows::{
+        w,
+        Win32::{
+            Foundation::{E_FAIL, E_NOINTERFACE, E_NOTIMPL},
+            System::{Com::IStream, WinRT::EventRegistrationToken},
+        },
+    };
+
+    use crate::{
+        pwstr::take_pwstr,
+        Microsoft::Web::WebView2::Win32::{
+            ICoreWebView2Environment_Impl, ICoreWebView2NewBrowserVersionAvailableEventHandler,
+            ICoreWebView2WebResourceResponse,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn default_options() {
+        let options = ControllerOptions::new().build();
+        let mut result = PWSTR(ptr::null_mut::<u16>());
+        unsafe { options.ProfileName(&mut result) }.unwrap();
+        assert_eq!(&take_pwstr(result), "");
+        let mut result = BOOL(1);
+        unsafe { options.IsInPrivateModeEnabled(&mut result) }.unwrap();
+        assert_eq!(result.0, 0);
+    }
+
+    #[test]
+    fn override_options() {
+        let options = ControllerOptions::new()
+            .profile_name("FakeProfile")
+            .in_private_mode_enabled(true)
+            .build();
+        let mut result = PWSTR(ptr::null_mut::<u16>());
+        unsafe { options.ProfileName(&mut result) }.unwrap();
+        assert_eq!(&take_pwstr(result), "FakeProfile");
+        let mut result = BOOL(0);
+        unsafe { options.IsInPrivateModeEnabled(&mut result) }.unwrap();
+        assert_eq!(result.0, 1);
+    }
+
+    #[test]
+    fn apply_options() {
+        let options: ICoreWebView2ControllerOptions =
+            CoreWebView2ControllerOptions::default().into();
+        unsafe { options.SetProfileName(w!("Unchanged")) }.unwrap();
+        ControllerOptions::new()
+            .in_private_mode_enabled(true)
+            .apply(&options)
+            .unwrap();
+        let mut result = PWSTR(ptr::null_mut::<u16>());
+        unsafe { options.ProfileName(&mut result) }.unwrap();
+        assert_eq!(&take_pwstr(result), "Unchanged");
+        let mut result = BOOL(0);
+        unsafe { options.IsInPrivateModeEnabled(&mut result) }.unwrap();
+        assert_eq!(result.0, 1);
+    }
+
+    /// Environment which only implements the original [`ICoreWebView2Environment`] interface,
+    /// and fails every request to create a controller.
+    #[implement(ICoreWebView2Environment)]
+    struct TestEnvironment(Rc<Cell<u32>>);
+
+    #[allow(non_snake_case)]
+    impl ICoreWebView2Environment_Impl for TestEnvironment {
+        fn CreateCoreWebView2Controller(
+            &self,
+            _parent: HWND,
+            handler: &Option<ICoreWebView2CreateCoreWebView2ControllerCompletedHandler>,
+        ) -> Result<()> {
+            self.0.set(self.0.get() + 1);
+            let handler = handler.as_ref().expect("handler is set");
+            unsafe { handler.Invoke(E_FAIL, None) }
+        }
+
+        fn CreateWebResourceResponse(
+            &self,
+            _content: &Option<IStream>,
+            _status_code: i32,
+            _reason_phrase: &PCWSTR,
+            _headers: &PCWSTR,
+        ) -> Result<ICoreWebView2WebResourceResponse> {
+            Err(E_NOTIMPL.into())
+        }
+
+        fn BrowserVersionString(&self, _version_info: *mut PWSTR) -> Result<()> {
+            Err(E_NOTIMPL.into())
+        }
+
+        fn add_NewBrowserVersionAvailable(
+            &self,
+            _event_handler: &Option<ICoreWebView2NewBrowserVersionAvailableEventHandler>,
+            _token: *mut EventRegistrationToken,
+        ) -> Result<()> {
+            Err(E_NOTIMPL.into())
+        }
+
+        fn remove_NewBrowserVersionAvailable(&self, _token: &EventRegistrationToken) -> Result<()> {
+            Err(E_NOTIMPL.into())
+        }
+    }
+
+    #[test]
+    fn fallback_with_default_options() {
+        let calls = Rc::new(Cell::new(0));
+        let environment: ICoreWebView2Environment = TestEnvironment(calls.clone()).into();
+        let result = create_controller(&environment, HWND::default(), &ControllerOptions::new());
+        assert!(matches!(result, Err(crate::Error::WindowsError(err)) if err.code() == E_FAIL));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn no_fallback_with_profile() {
+        let calls = Rc::new(Cell::new(0));
+        let environment: ICoreWebView2Environment = TestEnvironment(calls.clone()).into();
+        let options = ControllerOptions::new().profile_name("FakeProfile");
+        let result = create_controller(&environment, HWND::default(), &options);
+        assert!(
+            matches!(result, Err(crate::Error::WindowsError(err)) if err.code() == E_NOINTERFACE)
+        );
+        assert_eq!(calls.get(), 0);
+    }
+}
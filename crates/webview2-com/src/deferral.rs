@@ -0,0 +1,191 @@
+use std::future::Future;
+
+use windows::core::Interface;
+
+use crate::{EventClosure, LocalExecutor, Microsoft::Web::WebView2::Win32::*};
+
+/// Event args which can hand out an [`ICoreWebView2Deferral`], so the event can be handled after
+/// the `Invoke` call returns.
+pub trait DeferralEventArgs: Interface + Clone {
+    fn get_deferral(&self) -> windows::core::Result<ICoreWebView2Deferral>;
+}
+
+macro_rules! impl_deferral_event_args {
+    ($($args:ty),* $(,)?) => {
+        $(
+            impl DeferralEventArgs for $args {
+                fn get_deferral(&self) -> windows::core::Result<ICoreWebView2Deferral> {
+                    unsafe { self.GetDeferral() }
+                }
+            }
+        )*
+    };
+}
+
+impl_deferral_event_args!(
+    ICoreWebView2BasicAuthenticationRequestedEventArgs,
+    ICoreWebView2ClientCertificateRequestedEventArgs,
+    ICoreWebView2ContextMenuRequestedEventArgs,
+    ICoreWebView2DownloadStartingEventArgs,
+    ICoreWebView2NewWindowRequestedEventArgs,
+    ICoreWebView2NewWindowRequestedEventArgs2,
+    ICoreWebView2PermissionRequestedEventArgs,
+    ICoreWebView2PermissionRequestedEventArgs2,
+    ICoreWebView2ScriptDialogOpeningEventArgs,
+    ICoreWebView2ServerCertificateErrorDetectedEventArgs,
+    ICoreWebView2WebResourceRequestedEventArgs,
+);
+
+/// Calls `Complete` on the deferral when the task finishes, or when it is dropped without
+/// finishing, so WebView2 is never left waiting on it.
+struct CompleteOnDrop(Option<ICoreWebView2Deferral>);
+
+impl Drop for CompleteOnDrop {
+    fn drop(&mut self) {
+        if let Some(deferral) = self.0.take() {
+            let _ = unsafe { deferral.Complete() };
+        }
+    }
+}
+
+/// Wrap an async `handler` in an [`EventClosure`] for one of the event handlers whose args
+/// support [`DeferralEventArgs`]. Each time the event fires, the closure takes a deferral from
+/// the args, spawns the future returned by `handler` on `executor`, and returns to WebView2
+/// right away. The deferral is completed once the future resolves, so the handler can await a
+/// prompt or some other work without blocking the UI thread.
+pub fn deferred_event_closure<Sender, Args, F, Fut>(
+    executor: &LocalExecutor,
+    mut handler: F,
+) -> EventClosure<Option<Sender>, Option<Args>>
+where
+    Sender: Interface,
+    Args: DeferralEventArgs,
+    F: FnMut(Option<Sender>, Option<Args>) -> Fut + 'static,
+    Fut: Future<Output = ()> + 'static,
+{
+    let executor = executor.clone();
+    Box::new(move |sender, args| {
+        let deferral = CompleteOnDrop(args.as_ref().map(Args::get_deferral).transpose()?);
+        let future = handler(sender, args);
+        executor.spawn_local(async move {
+            let _deferral = deferral;
+            future.await;
+        });
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::Cell, rc::Rc};
+
+    use futures::channel::oneshot;
+    use windows::{
+        core::{PCWSTR, PWSTR},
+        Win32::Foundation::E_NOTIMPL,
+    };
+    use windows_implement::implement;
+
+    use super::*;
+    use crate::ScriptDialogOpeningEventHandler;
+
+    #[implement(ICoreWebView2Deferral)]
+    struct TestDeferral(Rc<Cell<u32>>);
+
+    #[allow(non_snake_case)]
+    impl ICoreWebView2Deferral_Impl for TestDeferral {
+        fn Complete(&self) -> windows::core::Result<()> {
+            self.0.set(self.0.get() + 1);
+            Ok(())
+        }
+    }
+
+    #[implement(ICoreWebView2ScriptDialogOpeningEventArgs)]
+    struct TestDialogArgs(ICoreWebView2Deferral);
+
+    #[allow(non_snake_case)]
+    impl ICoreWebView2ScriptDialogOpeningEventArgs_Impl for TestDialogArgs {
+        fn Uri(&self, _uri: *mut PWSTR) -> windows::core::Result<()> {
+            Err(E_NOTIMPL.into())
+        }
+
+        fn Kind(&self, _kind: *mut COREWEBVIEW2_SCRIPT_DIALOG_KIND) -> windows::core::Result<()> {
+            Err(E_NOTIMPL.into())
+        }
+
+        fn Message(&self, _message: *mut PWSTR) -> windows::core::Result<()> {
+            Err(E_NOTIMPL.into())
+        }
+
+        fn Accept(&self) -> windows::core::Result<()> {
+            Err(E_NOTIMPL.into())
+        }
+
+        fn DefaultText(&self, _default_text: *mut PWSTR) -> windows::core::Result<()> {
+            Err(E_NOTIMPL.into())
+        }
+
+        fn ResultText(&self, _result_text: *mut PWSTR) -> windows::core::Result<()> {
+            Err(E_NOTIMPL.into())
+        }
+
+        fn SetResultText(&self, _result_text: &PCWSTR) -> windows::core::Result<()> {
+            Err(E_NOTIMPL.into())
+        }
+
+        fn GetDeferral(&self) -> windows::core::Result<ICoreWebView2Deferral> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn test_args() -> (Rc<Cell<u32>>, ICoreWebView2ScriptDialogOpeningEventArgs) {
+        let completed = Rc::new(Cell::new(0));
+        let deferral = TestDeferral(completed.clone()).into();
+        (completed, TestDialogArgs(deferral).into())
+    }
+
+    #[test]
+    fn complete_after_future() {
+        let executor = LocalExecutor::new();
+        let (completed, args) = test_args();
+        let (tx, rx) = oneshot::channel::<()>();
+        let mut rx = Some(rx);
+
+        let handler = ScriptDialogOpeningEventHandler::create(deferred_event_closure(
+            &executor,
+            move |_sender, _args| {
+                let rx = rx.take().expect("event should only fire once");
+                async move {
+                    let _ = rx.await;
+                }
+            },
+        ));
+
+        unsafe { handler.Invoke(None, &args) }.unwrap();
+        executor.poll_ready();
+        assert_eq!(completed.get(), 0);
+
+        tx.send(()).unwrap();
+        executor.poll_ready();
+        assert_eq!(completed.get(), 1);
+    }
+
+    #[test]
+    fn complete_when_dropped() {
+        let executor = LocalExecutor::new();
+        let (completed, args) = test_args();
+
+        let handler = ScriptDialogOpeningEventHandler::create(deferred_event_closure(
+            &executor,
+            |_sender, _args| futures::future::pending(),
+        ));
+
+        unsafe { handler.Invoke(None, &args) }.unwrap();
+        executor.poll_ready();
+        assert_eq!(completed.get(), 0);
+
+        drop(handler);
+        drop(executor);
+        assert_eq!(completed.get(), 1);
+    }
+}
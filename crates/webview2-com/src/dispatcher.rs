@@ -0,0 +1,235 @@
+use std::{
+    collections::VecDeque,
+    mem,
+    sync::{Arc, Mutex},
+};
+
+use futures::channel::oneshot;
+use windows::Win32::{
+    Foundation::{HWND, LPARAM, WPARAM},
+    System::Threading,
+    UI::WindowsAndMessaging::{self, MSG},
+};
+
+type DispatcherJob = Box<dyn FnOnce() + Send>;
+
+/// Cross-thread handle for running jobs on the UI thread which created it. Jobs are queued from
+/// any thread with [`Dispatcher::dispatch`], which also posts `WM_APP` to the owning thread to
+/// kick its message loop, and they run the next time that thread calls
+/// [`Dispatcher::pump_dispatcher`].
+///
+/// Cloning a `Dispatcher` returns another handle to the same queue.
+#[derive(Clone)]
+pub struct Dispatcher {
+    thread_id: u32,
+    queue: Arc<Mutex<VecDeque<DispatcherJob>>>,
+}
+
+impl Dispatcher {
+    /// Create a dispatcher bound to the calling thread. This also makes sure the thread has a
+    /// message queue, so other threads can post to it right away.
+    pub fn new() -> Self {
+        let thread_id = unsafe {
+            let mut msg = MSG::default();
+            WindowsAndMessaging::PeekMessageA(
+                &mut msg,
+                HWND::default(),
+                0,
+                0,
+                WindowsAndMessaging::PM_NOREMOVE,
+            );
+            Threading::GetCurrentThreadId()
+        };
+
+        Self {
+            thread_id,
+            queue: Default::default(),
+        }
+    }
+
+    /// Get the id of the thread which owns this dispatcher.
+    pub fn thread_id(&self) -> u32 {
+        self.thread_id
+    }
+
+    /// Queue a job to run on the owning thread and kick its message loop. If the message can't be
+    /// posted, the job is dropped without running and the error is returned, so it is safe to
+    /// retry.
+    pub fn dispatch<F>(&self, f: F) -> crate::Result<()>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // Hold the lock until the message is posted, so the owning thread can't drain the queue
+        // in between, and the job is still at the back if we need to take it out again.
+        let mut queue = self.queue.lock().expect("lock dispatcher queue");
+        queue.push_back(Box::new(f));
+
+        self.post().map_err(|err| {
+            queue.pop_back();
+            crate::Error::from(err)
+        })
+    }
+
+    /// Queue a job to run on the owning thread, and get its result through a
+    /// [`oneshot::Receiver`]. The receiver is canceled if the job is dropped before it runs.
+    pub fn dispatch_with_result<F, T>(&self, f: F) -> crate::Result<oneshot::Receiver<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.dispatch(move || {
+            let _ = tx.send(f());
+        })?;
+        Ok(rx)
+    }
+
+    /// Run every job which was queued before this call, and return how many of them ran. Call
+    /// this from the message loop on the owning thread, e.g. whenever it retrieves `WM_APP`.
+    /// Jobs which are queued while this is running will post another `WM_APP` and wait for the
+    /// next call.
+    ///
+    /// Panics if it is called from any other thread.
+    pub fn pump_dispatcher(&self) -> usize {
+        assert_eq!(
+            self.thread_id,
+            unsafe { Threading::GetCurrentThreadId() },
+            "pump_dispatcher must be called on the thread which created the Dispatcher"
+        );
+
+        let mut pending = PendingJobs {
+            dispatcher: self,
+            jobs: mem::take(&mut *self.queue.lock().expect("lock dispatcher queue")),
+        };
+        let mut count = 0;
+
+        while let Some(job) = pending.jobs.pop_front() {
+            job();
+            count += 1;
+        }
+
+        count
+    }
+
+    fn post(&self) -> windows::core::Result<()> {
+        unsafe {
+            WindowsAndMessaging::PostThreadMessageW(
+                self.thread_id,
+                WindowsAndMessaging::WM_APP,
+                WPARAM::default(),
+                LPARAM::default(),
+            )
+        }
+        .ok()
+    }
+}
+
+/// Jobs which [`Dispatcher::pump_dispatcher`] took from the queue but has not run yet.
+struct PendingJobs<'a> {
+    dispatcher: &'a Dispatcher,
+    jobs: VecDeque<DispatcherJob>,
+}
+
+impl Drop for PendingJobs<'_> {
+    fn drop(&mut self) {
+        // If a job panicked, put the rest back at the front of the queue, and kick the message
+        // loop so they run on the next call instead of being dropped along with the panic.
+        if !self.jobs.is_empty() {
+            let mut queue = self.dispatcher.queue.lock().expect("lock dispatcher queue");
+            while let Some(job) = self.jobs.pop_back() {
+                queue.push_front(job);
+            }
+            let _ = self.dispatcher.post();
+        }
+    }
+}
+
+impl Default for Dispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        thread,
+    };
+
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn dispatch_from_other_thread() {
+        let dispatcher = Dispatcher::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let remote = dispatcher.clone();
+        thread::spawn(move || {
+            remote
+                .dispatch(move || {
+                    tx.send(unsafe { Threading::GetCurrentThreadId() })
+                        .expect("send over mpsc channel")
+                })
+                .unwrap()
+        })
+        .join()
+        .unwrap();
+
+        let mut msg = MSG::default();
+        unsafe { WindowsAndMessaging::GetMessageW(&mut msg, HWND::default(), 0, 0) };
+        assert_eq!(msg.message, WindowsAndMessaging::WM_APP);
+        assert_eq!(dispatcher.pump_dispatcher(), 1);
+        assert_eq!(rx.try_recv().unwrap(), dispatcher.thread_id());
+    }
+
+    #[test]
+    fn dispatch_with_result() {
+        let dispatcher = Dispatcher::new();
+        let remote = dispatcher.clone();
+        let rx = thread::spawn(move || remote.dispatch_with_result(|| 42).unwrap())
+            .join()
+            .unwrap();
+
+        assert_eq!(dispatcher.pump_dispatcher(), 1);
+        assert_eq!(block_on(rx).unwrap(), 42);
+    }
+
+    #[test]
+    fn failed_post_drops_job() {
+        let dispatcher = Dispatcher {
+            thread_id: u32::MAX,
+            queue: Default::default(),
+        };
+        assert!(dispatcher.dispatch(|| ()).is_err());
+        assert!(dispatcher.queue.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn panicking_job_keeps_the_rest() {
+        let dispatcher = Dispatcher::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let first = tx.clone();
+        dispatcher.dispatch(move || first.send(1).unwrap()).unwrap();
+        dispatcher.dispatch(|| panic!("job panicked")).unwrap();
+        dispatcher.dispatch(move || tx.send(3).unwrap()).unwrap();
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| dispatcher.pump_dispatcher()));
+        assert!(result.is_err());
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1]);
+
+        assert_eq!(dispatcher.pump_dispatcher(), 1);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![3]);
+    }
+
+    #[test]
+    fn dropped_job_cancels_result() {
+        let dispatcher = Dispatcher::new();
+        let rx = dispatcher.dispatch_with_result(|| 42).unwrap();
+        drop(dispatcher);
+        assert!(block_on(rx).is_err());
+    }
+}
@@ -0,0 +1,341 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    future::Future,
+    mem,
+    pin::Pin,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Wake, Waker},
+};
+
+use windows::Win32::{
+    Foundation::{HWND, LPARAM, WPARAM},
+    System::Threading,
+    UI::WindowsAndMessaging::{self, MSG},
+};
+
+use crate::{Error, Result};
+
+type LocalTask = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Reserved task id for the future passed to [`LocalExecutor::run_until`].
+const MAIN_TASK: usize = 0;
+
+/// Queue of woken task ids shared with every [`Waker`]. This is the only part of the executor
+/// which is touched from other threads, so it posts `WM_APP` to the owning thread to kick the
+/// message loop after queueing a task.
+struct WakeQueue {
+    thread_id: u32,
+    ready: Mutex<VecDeque<usize>>,
+    posted: AtomicBool,
+}
+
+impl WakeQueue {
+    fn schedule(&self, id: usize) {
+        self.ready.lock().expect("lock ready queue").push_back(id);
+
+        if !self.posted.swap(true, Ordering::AcqRel) {
+            unsafe {
+                WindowsAndMessaging::PostThreadMessageA(
+                    self.thread_id,
+                    WindowsAndMessaging::WM_APP,
+                    WPARAM::default(),
+                    LPARAM::default(),
+                );
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<usize> {
+        self.ready.lock().expect("lock ready queue").pop_front()
+    }
+}
+
+struct TaskWaker {
+    id: usize,
+    queue: Arc<WakeQueue>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.queue.schedule(self.id);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.queue.schedule(self.id);
+    }
+}
+
+struct ExecutorState {
+    queue: Arc<WakeQueue>,
+    tasks: RefCell<HashMap<usize, LocalTask>>,
+    next_id: Cell<usize>,
+    closed: Cell<bool>,
+}
+
+/// Single-threaded executor for `!Send` futures which runs on the UI (STA) thread and shares the
+/// Win32 message loop with WebView2. Wakers post `WM_APP` to the owning thread, and ready tasks
+/// are polled in between dispatched messages, so async code never needs to nest calls to
+/// [`crate::wait_with_pump`].
+///
+/// Cloning a `LocalExecutor` returns another handle to the same executor, which is how tasks can
+/// call [`LocalExecutor::spawn_local`] themselves. The executor shuts down when the handle from
+/// [`LocalExecutor::new`] is dropped: any tasks which have not finished are dropped with it, even
+/// if they hold a clone, and later calls to `spawn_local` on a clone drop the future right away.
+pub struct LocalExecutor {
+    state: Rc<ExecutorState>,
+    owner: bool,
+}
+
+impl LocalExecutor {
+    /// Create an executor bound to the calling thread. This also makes sure the thread has a
+    /// message queue, so wakers on other threads can post to it right away.
+    pub fn new() -> Self {
+        let thread_id = unsafe {
+            let mut msg = MSG::default();
+            WindowsAndMessaging::PeekMessageA(
+                &mut msg,
+                HWND::default(),
+                0,
+                0,
+                WindowsAndMessaging::PM_NOREMOVE,
+            );
+            Threading::GetCurrentThreadId()
+        };
+
+        Self {
+            state: Rc::new(ExecutorState {
+                queue: Arc::new(WakeQueue {
+                    thread_id,
+                    ready: Mutex::new(VecDeque::new()),
+                    posted: AtomicBool::new(false),
+                }),
+                tasks: RefCell::new(HashMap::new()),
+                next_id: Cell::new(MAIN_TASK + 1),
+                closed: Cell::new(false),
+            }),
+            owner: true,
+        }
+    }
+
+    /// Queue a `!Send` future to run on this thread. It is first polled the next time the
+    /// executor gets a chance to run, not from inside `spawn_local`. If the executor has already
+    /// shut down, the future is dropped without being polled.
+    pub fn spawn_local<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        if self.state.closed.get() {
+            return;
+        }
+
+        let id = self.state.next_id.get();
+        self.state.next_id.set(id + 1);
+        self.state.tasks.borrow_mut().insert(id, Box::pin(future));
+        self.state.queue.schedule(id);
+    }
+
+    /// Poll every task which has been woken since the last time this was called. Call this after
+    /// dispatching messages if you run your own message loop instead of [`LocalExecutor::run`].
+    pub fn poll_ready(&self) {
+        self.poll_tasks();
+    }
+
+    /// Run the message loop and poll spawned tasks until `future` completes. Returns
+    /// [`Error::TaskCanceled`] if the message loop receives `WM_QUIT` first.
+    pub fn run_until<F>(&self, future: F) -> Result<F::Output>
+    where
+        F: Future,
+    {
+        let mut future = Box::pin(future);
+        let waker = self.waker(MAIN_TASK);
+        let mut context = Context::from_waker(&waker);
+        let mut main_ready = true;
+
+        loop {
+            if main_ready {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                    return Ok(output);
+                }
+            }
+
+            main_ready = self.poll_tasks();
+            if !main_ready {
+                self.pump_message()?;
+            }
+        }
+    }
+
+    /// Run the message loop and poll spawned tasks until it receives `WM_QUIT`.
+    pub fn run(&self) -> Result<()> {
+        loop {
+            self.poll_tasks();
+
+            match self.pump_message() {
+                Err(Error::TaskCanceled) => return Ok(()),
+                result => result?,
+            }
+        }
+    }
+
+    fn waker(&self, id: usize) -> Waker {
+        Waker::from(Arc::new(TaskWaker {
+            id,
+            queue: self.state.queue.clone(),
+        }))
+    }
+
+    /// Drain the ready queue, and report whether the [`MAIN_TASK`] was woken along the way.
+    fn poll_tasks(&self) -> bool {
+        self.state.queue.posted.store(false, Ordering::Release);
+        let mut main_ready = false;
+
+        while let Some(id) = self.state.queue.pop() {
+            if id == MAIN_TASK {
+                main_ready = true;
+                continue;
+            }
+
+            // The task is removed while it is polled, so it can spawn more tasks, and duplicate
+            // wakeups for a task which already finished are ignored.
+            let task = self.state.tasks.borrow_mut().remove(&id);
+            if let Some(mut task) = task {
+                let waker = self.waker(id);
+                let mut context = Context::from_waker(&waker);
+                if task.as_mut().poll(&mut context).is_pending() {
+                    self.state.tasks.borrow_mut().insert(id, task);
+                }
+            }
+        }
+
+        main_ready
+    }
+
+    fn pump_message(&self) -> Result<()> {
+        let mut msg = MSG::default();
+
+        unsafe {
+            match WindowsAndMessaging::GetMessageA(&mut msg, HWND::default(), 0, 0).0 {
+                -1 => Err(windows::core::Error::from_win32().into()),
+                0 => Err(Error::TaskCanceled),
+                _ => {
+                    WindowsAndMessaging::TranslateMessage(&msg);
+                    WindowsAndMessaging::DispatchMessageA(&msg);
+                    crate::resume_callback_panic();
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+impl Clone for LocalExecutor {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            owner: false,
+        }
+    }
+}
+
+impl Default for LocalExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for LocalExecutor {
+    fn drop(&mut self) {
+        if self.owner {
+            // Tasks may hold a clone of the executor, so drop them here to break the cycle. The
+            // map is moved out first because dropping a task drops those clones too.
+            self.state.closed.set(true);
+            let tasks = mem::take(&mut *self.state.tasks.borrow_mut());
+            drop(tasks);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{rc::Rc, thread};
+
+    use futures::channel::oneshot;
+    use windows::Win32::UI::WindowsAndMessaging;
+
+    use super::*;
+
+    #[test]
+    fn run_until_ready() {
+        let executor = LocalExecutor::new();
+        assert_eq!(executor.run_until(async { 42 }).unwrap(), 42);
+    }
+
+    #[test]
+    fn spawn_local_tasks() {
+        let executor = LocalExecutor::new();
+        let (tx, rx) = oneshot::channel();
+        let value = Rc::new(Cell::new(0));
+
+        let spawner = executor.clone();
+        let counter = value.clone();
+        executor.spawn_local(async move {
+            counter.set(1);
+            spawner.spawn_local(async move {
+                counter.set(counter.get() + 1);
+                tx.send(()).expect("send over oneshot channel");
+            });
+        });
+
+        executor.run_until(rx).unwrap().unwrap();
+        assert_eq!(value.get(), 2);
+    }
+
+    #[test]
+    fn wake_from_other_thread() {
+        let executor = LocalExecutor::new();
+        let (tx, rx) = oneshot::channel();
+
+        let sender = thread::spawn(move || tx.send(42).expect("send over oneshot channel"));
+        let result = executor.run_until(rx).unwrap().unwrap();
+        sender.join().unwrap();
+
+        assert_eq!(result, 42);
+    }
+
+    #[test]
+    fn drop_pending_tasks() {
+        let executor = LocalExecutor::new();
+        let alive = Rc::new(());
+
+        let spawner = executor.clone();
+        let captured = alive.clone();
+        executor.spawn_local(async move {
+            let _captured = captured;
+            spawner.spawn_local(futures::future::pending());
+        });
+        assert_eq!(Rc::strong_count(&alive), 2);
+
+        let spawner = executor.clone();
+        drop(executor);
+        assert_eq!(Rc::strong_count(&alive), 1);
+
+        let captured = alive.clone();
+        spawner.spawn_local(async move {
+            let _captured = captured;
+        });
+        assert_eq!(Rc::strong_count(&alive), 1);
+    }
+
+    #[test]
+    fn run_until_quit() {
+        let executor = LocalExecutor::new();
+        unsafe { WindowsAndMessaging::PostQuitMessage(0) };
+        let result = executor.run_until(futures::future::pending::<()>());
+        assert!(matches!(result, Err(Error::TaskCanceled)));
+    }
+}
@@ -5,34 +5,175 @@ pub use webview2_com_sys::Microsoft;
 extern crate webview2_com_macros;
 
 mod callback;
+mod cancellation;
+mod collections;
+mod controller;
+mod deferral;
+mod dispatcher;
+mod executor;
 mod options;
 mod pwstr;
+mod registration;
+mod runtime;
+mod unwind;
 
-use std::{fmt, sync::mpsc};
+use std::{
+    fmt,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
 
 use windows::{
     core::HRESULT,
     Win32::{
-        Foundation::HWND,
+        Foundation::{
+            ERROR_FILE_NOT_FOUND, ERROR_INVALID_STATE, ERROR_LOCK_VIOLATION,
+            ERROR_SHARING_VIOLATION, E_ABORT, E_OUTOFMEMORY, HANDLE, HWND, WAIT_FAILED,
+        },
         UI::WindowsAndMessaging::{self, MSG},
     },
 };
 
 pub use callback::*;
+pub use cancellation::*;
+pub use collections::*;
+pub use controller::*;
+pub use deferral::*;
+pub use dispatcher::*;
+pub use executor::*;
 pub use options::*;
 pub use pwstr::*;
+pub use registration::*;
+pub use runtime::*;
+pub use unwind::*;
+pub use webview2_com_util::{
+    os_string_from_pcwstr, pwstr_from_str_in, string_from_pcwstr, take_pwstr_in,
+    try_pwstr_from_str_in, try_string_from_pcwstr, try_take_pwstr_in, BrowserArguments,
+    BrowserChannel, BrowserVersion, CoTaskMemMut, CoTaskMemRef, ComAllocator, ComString,
+};
 
+/// Errors returned by this crate.
+///
+/// Converting a [`windows::core::Error`] or [`HRESULT`] with `From` always gives a
+/// [`Error::WindowsError`]. [`EnvironmentOptionsBuilder::create_environment`] and [`create_controller`]
+/// map the codes which WebView2 documents for those calls to named variants, so an app can show
+/// a meaningful message. Use [`Error::code`] to get the `HRESULT` from any of them.
 #[derive(Debug)]
 pub enum Error {
     WindowsError(windows::core::Error),
-    CallbackError(String),
+    /// `HRESULT_FROM_WIN32(ERROR_FILE_NOT_FOUND)`: there is no WebView2 Runtime installed, or no
+    /// runtime in the `browserExecutableFolder`.
+    RuntimeNotInstalled(windows::core::Error),
+    /// `HRESULT_FROM_WIN32(ERROR_SHARING_VIOLATION)` or `ERROR_LOCK_VIOLATION`: another process
+    /// is holding the user data folder.
+    UserDataFolderLocked(windows::core::Error),
+    /// `E_ABORT`: the controller or its parent window was closed before the operation finished.
+    ControllerClosed(windows::core::Error),
+    /// `HRESULT_FROM_WIN32(ERROR_INVALID_STATE)`: e.g. the environment options do not match a
+    /// browser process which is already using the same user data folder.
+    InvalidState(windows::core::Error),
+    CallbackError(CallbackError),
     TaskCanceled,
     SendError,
+    Timeout,
+    InvalidArguments(String),
+    InvalidVersion(String),
+    RuntimeNotFound(String),
+    InvalidString(String),
+}
+
+impl Error {
+    /// The `HRESULT` behind this error, if it came from a Windows or WebView2 API.
+    pub fn code(&self) -> Option<HRESULT> {
+        self.windows_error().map(windows::core::Error::code)
+    }
+
+    /// Name the codes which `CreateCoreWebView2EnvironmentWithOptions` documents.
+    pub(crate) fn environment_creation_error(self) -> Self {
+        match self {
+            Error::WindowsError(err) => match err.code() {
+                code if code == ERROR_FILE_NOT_FOUND.to_hresult() => {
+                    Error::RuntimeNotInstalled(err)
+                }
+                code if code == ERROR_SHARING_VIOLATION.to_hresult()
+                    || code == ERROR_LOCK_VIOLATION.to_hresult() =>
+                {
+                    Error::UserDataFolderLocked(err)
+                }
+                code if code == ERROR_INVALID_STATE.to_hresult() => Error::InvalidState(err),
+                _ => Error::WindowsError(err),
+            },
+            err => err,
+        }
+    }
+
+    /// Name the codes which `CreateCoreWebView2Controller` documents.
+    pub(crate) fn controller_creation_error(self) -> Self {
+        match self {
+            Error::WindowsError(err) if err.code() == E_ABORT => Error::ControllerClosed(err),
+            err => err,
+        }
+    }
+
+    fn windows_error(&self) -> Option<&windows::core::Error> {
+        match self {
+            Error::WindowsError(err)
+            | Error::RuntimeNotInstalled(err)
+            | Error::UserDataFolderLocked(err)
+            | Error::ControllerClosed(err)
+            | Error::InvalidState(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Error::WindowsError(err) => write!(f, "{} ({:#010X})", err.message(), err.code().0),
+            Error::RuntimeNotInstalled(err) => write!(
+                f,
+                "the WebView2 Runtime is not installed ({:#010X})",
+                err.code().0
+            ),
+            Error::UserDataFolderLocked(err) => write!(
+                f,
+                "the user data folder is locked by another process ({:#010X})",
+                err.code().0
+            ),
+            Error::ControllerClosed(err) => write!(
+                f,
+                "the WebView2 controller was closed before the operation finished ({:#010X})",
+                err.code().0
+            ),
+            Error::InvalidState(err) => write!(
+                f,
+                "WebView2 is in an invalid state for this call ({:#010X})",
+                err.code().0
+            ),
+            Error::CallbackError(err) => write!(f, "{}", err),
+            Error::TaskCanceled => f.write_str("the message loop quit before the task finished"),
+            Error::SendError => f.write_str("the result could not be sent to the receiver"),
+            Error::Timeout => f.write_str("timed out waiting for the result"),
+            Error::InvalidArguments(message) => write!(f, "invalid browser arguments: {}", message),
+            Error::InvalidVersion(version) => write!(f, "invalid browser version: {}", version),
+            Error::RuntimeNotFound(message) => {
+                write!(f, "fixed-version WebView2 Runtime not found: {}", message)
+            }
+            Error::InvalidString(message) => write!(f, "invalid string: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::WindowsError(_) => None,
+            Error::CallbackError(err) => err.source(),
+            _ => self
+                .windows_error()
+                .map(|err| err as &(dyn std::error::Error + 'static)),
+        }
     }
 }
 
@@ -44,7 +185,68 @@ impl From<windows::core::Error> for Error {
 
 impl From<HRESULT> for Error {
     fn from(err: HRESULT) -> Self {
-        Self::WindowsError(windows::core::Error::from(err))
+        windows::core::Error::from(err).into()
+    }
+}
+
+impl From<webview2_com_util::Error> for Error {
+    fn from(err: webview2_com_util::Error) -> Self {
+        match err {
+            webview2_com_util::Error::InvalidArguments(message) => Self::InvalidArguments(message),
+            webview2_com_util::Error::InvalidVersion(version) => Self::InvalidVersion(version),
+            webview2_com_util::Error::InvalidString(message) => Self::InvalidString(message),
+            webview2_com_util::Error::OutOfMemory => E_OUTOFMEMORY.into(),
+        }
+    }
+}
+
+impl From<CallbackError> for Error {
+    fn from(err: CallbackError) -> Self {
+        Self::CallbackError(err)
+    }
+}
+
+/// Error reported by an app callback, e.g. a host object method which was called from script,
+/// with an optional cause which is returned by [`std::error::Error::source`].
+#[derive(Debug)]
+pub struct CallbackError {
+    message: String,
+    source: Option<Box<dyn std::error::Error>>,
+}
+
+impl CallbackError {
+    pub fn new<T: Into<String>>(message: T) -> Self {
+        Self {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub fn with_source<T, E>(message: T, source: E) -> Self
+    where
+        T: Into<String>,
+        E: Into<Box<dyn std::error::Error>>,
+    {
+        Self {
+            message: message.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for CallbackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CallbackError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_deref()
     }
 }
 
@@ -58,13 +260,16 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// `GetMessage` is a blocking call, so if we want to send results from another thread, senders from other
 /// threads should "kick" the message loop after sending the result by calling `PostThreadMessage` with an
 /// ignorable/unhandled message such as `WM_APP`.
+///
+/// If every sender is dropped before sending a result, e.g. because a completed handler panicked
+/// with [`PanicPolicy::Log`], it returns [`Error::CallbackError`] instead of waiting forever.
 pub fn wait_with_pump<T>(rx: mpsc::Receiver<T>) -> Result<T> {
     let mut msg = MSG::default();
     let hwnd = HWND::default();
 
     loop {
-        if let Ok(result) = rx.try_recv() {
-            return Ok(result);
+        if let Some(result) = try_recv_result(&rx) {
+            return result;
         }
 
         unsafe {
@@ -76,8 +281,251 @@ pub fn wait_with_pump<T>(rx: mpsc::Receiver<T>) -> Result<T> {
                 _ => {
                     WindowsAndMessaging::TranslateMessage(&msg);
                     WindowsAndMessaging::DispatchMessageA(&msg);
+                    resume_callback_panic();
+                }
+            }
+        }
+    }
+}
+
+/// Check for a result in `rx` without blocking. A disconnected channel will never get one.
+fn try_recv_result<T>(rx: &mpsc::Receiver<T>) -> Option<Result<T>> {
+    match rx.try_recv() {
+        Ok(result) => Some(Ok(result)),
+        Err(mpsc::TryRecvError::Empty) => None,
+        Err(mpsc::TryRecvError::Disconnected) => Some(Err(CallbackError::new(
+            "the callback was dropped without sending a result",
+        )
+        .into())),
+    }
+}
+
+/// Like [`wait_with_pump`], but it gives up and returns [`Error::Timeout`] if there is still no
+/// result in `rx` after `timeout`.
+pub fn wait_with_pump_timeout<T>(rx: mpsc::Receiver<T>, timeout: Duration) -> Result<T> {
+    wait_with_pump_until(rx, Some(Instant::now() + timeout), None)
+}
+
+/// Like [`wait_with_pump`], but it gives up and returns [`Error::TaskCanceled`] as soon as
+/// `cancel` is signaled, even if that happens on another thread.
+pub fn wait_with_pump_cancellable<T>(
+    rx: mpsc::Receiver<T>,
+    cancel: &CancellationToken,
+) -> Result<T> {
+    wait_with_pump_until(rx, None, Some(cancel))
+}
+
+/// Common implementation of [`wait_with_pump_timeout`] and [`wait_with_pump_cancellable`]. Instead
+/// of blocking in `GetMessage`, it uses `MsgWaitForMultipleObjectsEx` to wait for the next message,
+/// the `cancel` event, or the `deadline`, whichever comes first. With neither a `deadline` nor a
+/// `cancel` token, it behaves the same as [`wait_with_pump`].
+///
+/// If it retrieves `WM_QUIT`, it posts it again before returning [`Error::TaskCanceled`], so the
+/// outer message loop still sees it and exits.
+pub fn wait_with_pump_until<T>(
+    rx: mpsc::Receiver<T>,
+    deadline: Option<Instant>,
+    cancel: Option<&CancellationToken>,
+) -> Result<T> {
+    const INFINITE: u32 = u32::MAX;
+
+    let mut msg = MSG::default();
+    let hwnd = HWND::default();
+    let handles: Vec<HANDLE> = cancel.iter().map(|cancel| cancel.handle()).collect();
+
+    loop {
+        if let Some(result) = try_recv_result(&rx) {
+            return result;
+        }
+
+        if cancel.map_or(false, CancellationToken::is_canceled) {
+            return Err(Error::TaskCanceled);
+        }
+
+        let timeout = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(Error::Timeout);
                 }
+
+                // Round up so we don't spin on a zero timeout for the last partial millisecond.
+                let millis = (remaining.as_micros() + 999) / 1000;
+                millis.min(u128::from(INFINITE - 1)) as u32
+            }
+            None => INFINITE,
+        };
+
+        unsafe {
+            if WindowsAndMessaging::PeekMessageA(
+                &mut msg,
+                hwnd,
+                0,
+                0,
+                WindowsAndMessaging::PM_REMOVE,
+            )
+            .as_bool()
+            {
+                if msg.message == WindowsAndMessaging::WM_QUIT {
+                    WindowsAndMessaging::PostQuitMessage(msg.wParam.0 as i32);
+                    return Err(Error::TaskCanceled);
+                }
+
+                WindowsAndMessaging::TranslateMessage(&msg);
+                WindowsAndMessaging::DispatchMessageA(&msg);
+                resume_callback_panic();
+                continue;
+            }
+
+            // Whether it woke up for a message, the deadline, or the cancel token, that will be
+            // handled at the top of the loop. `MWMO_INPUTAVAILABLE` makes it return right away
+            // for input which is already in the queue, even if something else has peeked at it.
+            if WindowsAndMessaging::MsgWaitForMultipleObjectsEx(
+                &handles,
+                timeout,
+                WindowsAndMessaging::QS_ALLINPUT,
+                WindowsAndMessaging::MWMO_INPUTAVAILABLE,
+            ) == WAIT_FAILED.0
+            {
+                return Err(windows::core::Error::from_win32().into());
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn pump_timeout() {
+        let (_tx, rx) = mpsc::channel::<()>();
+        let result = wait_with_pump_timeout(rx, Duration::from_millis(10));
+        assert!(matches!(result, Err(Error::Timeout)));
+    }
+
+    #[test]
+    fn pump_cancel() {
+        let (_tx, rx) = mpsc::channel::<()>();
+        let cancel = CancellationToken::new().unwrap();
+        let canceler = cancel.clone();
+        let thread = thread::spawn(move || canceler.cancel());
+        let result = wait_with_pump_cancellable(rx, &cancel);
+        thread.join().unwrap();
+        assert!(matches!(result, Err(Error::TaskCanceled)));
+        assert!(cancel.is_canceled());
+    }
+
+    #[test]
+    fn named_hresults() {
+        use windows::Win32::Foundation::{E_FAIL, E_POINTER};
+
+        let environment_error = |code: HRESULT| Error::from(code).environment_creation_error();
+        assert!(matches!(
+            environment_error(ERROR_FILE_NOT_FOUND.to_hresult()),
+            Error::RuntimeNotInstalled(_)
+        ));
+        assert!(matches!(
+            environment_error(ERROR_SHARING_VIOLATION.to_hresult()),
+            Error::UserDataFolderLocked(_)
+        ));
+        assert!(matches!(
+            environment_error(ERROR_LOCK_VIOLATION.to_hresult()),
+            Error::UserDataFolderLocked(_)
+        ));
+        assert!(matches!(
+            environment_error(ERROR_INVALID_STATE.to_hresult()),
+            Error::InvalidState(_)
+        ));
+        assert!(matches!(environment_error(E_ABORT), Error::WindowsError(_)));
+        assert!(matches!(environment_error(E_FAIL), Error::WindowsError(_)));
+
+        let controller_error = |code: HRESULT| Error::from(code).controller_creation_error();
+        assert!(matches!(
+            controller_error(E_ABORT),
+            Error::ControllerClosed(_)
+        ));
+        assert!(matches!(
+            controller_error(ERROR_FILE_NOT_FOUND.to_hresult()),
+            Error::WindowsError(_)
+        ));
+        assert!(matches!(
+            Error::Timeout.controller_creation_error(),
+            Error::Timeout
+        ));
+
+        // Other APIs can fail with the same codes for unrelated reasons.
+        assert!(matches!(
+            ERROR_FILE_NOT_FOUND.to_hresult().into(),
+            Error::WindowsError(_)
+        ));
+        assert!(matches!(E_ABORT.into(), Error::WindowsError(_)));
+
+        assert_eq!(Error::from(E_ABORT).code(), Some(E_ABORT));
+        assert_eq!(controller_error(E_ABORT).code(), Some(E_ABORT));
+        assert_eq!(Error::from(E_POINTER).code(), Some(E_POINTER));
+        assert_eq!(Error::Timeout.code(), None);
+    }
+
+    #[test]
+    fn error_sources() {
+        use std::error::Error as _;
+
+        let err = Error::from(ERROR_FILE_NOT_FOUND.to_hresult()).environment_creation_error();
+        assert_eq!(
+            &err.to_string(),
+            "the WebView2 Runtime is not installed (0x80070002)"
+        );
+        let source = err.source().expect("named variants have a source");
+        let source = source
+            .downcast_ref::<windows::core::Error>()
+            .expect("source is the windows::core::Error");
+        assert_eq!(source.code(), ERROR_FILE_NOT_FOUND.to_hresult());
+
+        let cause = Error::InvalidVersion("104".to_string());
+        let err: Error = CallbackError::with_source("failed to check the version", cause).into();
+        assert_eq!(&err.to_string(), "failed to check the version");
+        assert_eq!(
+            &err.source().unwrap().to_string(),
+            "invalid browser version: 104"
+        );
+        assert!(Error::from(CallbackError::new("no cause"))
+            .source()
+            .is_none());
+    }
+
+    #[test]
+    fn pump_reposts_quit() {
+        let (_tx, rx) = mpsc::channel::<()>();
+        unsafe { WindowsAndMessaging::PostQuitMessage(7) };
+        let result = wait_with_pump_timeout(rx, Duration::from_secs(60));
+        assert!(matches!(result, Err(Error::TaskCanceled)));
+
+        let mut msg = MSG::default();
+        let result = unsafe { WindowsAndMessaging::GetMessageA(&mut msg, HWND::default(), 0, 0) };
+        assert_eq!(result.0, 0);
+        assert_eq!(msg.wParam.0, 7);
+    }
+
+    #[test]
+    fn pump_disconnected() {
+        let (tx, rx) = mpsc::channel::<()>();
+        drop(tx);
+        assert!(matches!(wait_with_pump(rx), Err(Error::CallbackError(_))));
+
+        let (tx, rx) = mpsc::channel::<()>();
+        drop(tx);
+        let result = wait_with_pump_timeout(rx, Duration::from_secs(60));
+        assert!(matches!(result, Err(Error::CallbackError(_))));
+    }
+
+    #[test]
+    fn pump_result_before_deadline() {
+        let (tx, rx) = mpsc::channel();
+        tx.send(42).unwrap();
+        let result = wait_with_pump_timeout(rx, Duration::from_secs(60)).unwrap();
+        assert_eq!(result, 42);
+    }
+}
@@ -1,117 +1,369 @@
-use std::{cell::UnsafeCell, default::Default};
+use std::{
+    default::Default,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Mutex, MutexGuard, PoisonError,
+    },
+};
 
 use windows::{
-    core::{Result, PCWSTR, PWSTR},
+    core::{Result, HSTRING, PCWSTR, PWSTR},
     Win32::Foundation::{BOOL, E_POINTER},
 };
 
 use windows_implement::implement;
 
+use webview2_com_util::{string_from_pcwstr, ComAllocator};
+
 use crate::{
-    pwstr::{pwstr_from_str, string_from_pcwstr},
+    pwstr::{out_pwstr_from_str_in, CoTaskMemAllocator},
+    BrowserArguments, CreateCoreWebView2EnvironmentCompletedHandler,
     Microsoft::Web::WebView2::Win32::{
-        ICoreWebView2EnvironmentOptions, ICoreWebView2EnvironmentOptions_Impl,
+        CreateCoreWebView2EnvironmentWithOptions, ICoreWebView2Environment,
+        ICoreWebView2EnvironmentOptions, ICoreWebView2EnvironmentOptions2,
+        ICoreWebView2EnvironmentOptions2_Impl, ICoreWebView2EnvironmentOptions_Impl,
         CORE_WEBVIEW_TARGET_PRODUCT_VERSION,
     },
 };
 
-#[implement(ICoreWebView2EnvironmentOptions)]
+/// Implementation of [`ICoreWebView2EnvironmentOptions`] and the newer `EnvironmentOptionsN`
+/// interfaces. The fields are private and only reachable through the COM interfaces, so support
+/// for each new version of the interface is added without changing how the struct is created.
+///
+/// The strings are guarded by a [`Mutex`] and the flags are [`AtomicBool`]s, so the struct is
+/// `Send + Sync`. It can be prepared on a worker thread and then moved to the UI thread, where it
+/// is converted to the COM interface.
+#[implement(ICoreWebView2EnvironmentOptions, ICoreWebView2EnvironmentOptions2)]
 pub struct CoreWebView2EnvironmentOptions {
-    additional_browser_arguments: UnsafeCell<String>,
-    language: UnsafeCell<String>,
-    target_compatible_browser_version: UnsafeCell<String>,
-    allow_single_sign_on_using_os_primary_account: UnsafeCell<bool>,
+    additional_browser_arguments: Mutex<String>,
+    language: Mutex<String>,
+    target_compatible_browser_version: Mutex<String>,
+    allow_single_sign_on_using_os_primary_account: AtomicBool,
+    exclusive_user_data_folder_access: AtomicBool,
 }
 
-impl Default for CoreWebView2EnvironmentOptions {
+/// Plain copy of the values in [`CoreWebView2EnvironmentOptions`] at one point in time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvironmentOptionsSnapshot {
+    pub additional_browser_arguments: String,
+    pub language: String,
+    pub target_compatible_browser_version: String,
+    pub allow_single_sign_on_using_os_primary_account: bool,
+    pub exclusive_user_data_folder_access: bool,
+}
+
+impl Default for EnvironmentOptionsSnapshot {
     fn default() -> Self {
         Self {
-            additional_browser_arguments: String::new().into(),
-            language: String::new().into(),
-            target_compatible_browser_version: CORE_WEBVIEW_TARGET_PRODUCT_VERSION
-                .to_string()
-                .into(),
-            allow_single_sign_on_using_os_primary_account: false.into(),
+            additional_browser_arguments: String::new(),
+            language: String::new(),
+            target_compatible_browser_version: CORE_WEBVIEW_TARGET_PRODUCT_VERSION.to_string(),
+            allow_single_sign_on_using_os_primary_account: false,
+            exclusive_user_data_folder_access: false,
         }
     }
 }
 
-#[allow(non_snake_case)]
+impl From<EnvironmentOptionsSnapshot> for CoreWebView2EnvironmentOptions {
+    fn from(snapshot: EnvironmentOptionsSnapshot) -> Self {
+        Self {
+            additional_browser_arguments: Mutex::new(snapshot.additional_browser_arguments),
+            language: Mutex::new(snapshot.language),
+            target_compatible_browser_version: Mutex::new(
+                snapshot.target_compatible_browser_version,
+            ),
+            allow_single_sign_on_using_os_primary_account: AtomicBool::new(
+                snapshot.allow_single_sign_on_using_os_primary_account,
+            ),
+            exclusive_user_data_folder_access: AtomicBool::new(
+                snapshot.exclusive_user_data_folder_access,
+            ),
+        }
+    }
+}
+
+impl Default for CoreWebView2EnvironmentOptions {
+    fn default() -> Self {
+        EnvironmentOptionsSnapshot::default().into()
+    }
+}
+
+impl CoreWebView2EnvironmentOptions {
+    /// Copy all of the current values.
+    pub fn snapshot(&self) -> EnvironmentOptionsSnapshot {
+        EnvironmentOptionsSnapshot {
+            additional_browser_arguments: lock_string(&self.additional_browser_arguments).clone(),
+            language: lock_string(&self.language).clone(),
+            target_compatible_browser_version: lock_string(&self.target_compatible_browser_version)
+                .clone(),
+            allow_single_sign_on_using_os_primary_account: self
+                .allow_single_sign_on_using_os_primary_account
+                .load(Ordering::Acquire),
+            exclusive_user_data_folder_access: self
+                .exclusive_user_data_folder_access
+                .load(Ordering::Acquire),
+        }
+    }
+
+    /// Parse the current `AdditionalBrowserArguments` into [`BrowserArguments`].
+    pub fn browser_arguments(&self) -> crate::Result<BrowserArguments> {
+        BrowserArguments::parse(lock_string(&self.additional_browser_arguments).as_str())
+            .map_err(crate::Error::from)
+    }
+
+    /// Replace `AdditionalBrowserArguments` with the serialized [`BrowserArguments`].
+    pub fn set_browser_arguments(&self, value: &BrowserArguments) {
+        *lock_string(&self.additional_browser_arguments) = value.to_string();
+    }
+}
+
+/// None of the setters can panic while they hold the lock, so a poisoned lock still holds a
+/// complete value.
+pub(crate) fn lock_string(value: &Mutex<String>) -> MutexGuard<'_, String> {
+    value.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub(crate) fn get_string(value: &Mutex<String>, result: *mut PWSTR) -> Result<()> {
+    get_string_in::<CoTaskMemAllocator>(value, result)
+}
+
+/// Like [`get_string`], but allocate the result with `A`.
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub(crate) fn get_string_in<A: ComAllocator>(
+    value: &Mutex<String>,
+    result: *mut PWSTR,
+) -> Result<()> {
+    if result.is_null() {
+        E_POINTER.ok()
+    } else {
+        unsafe { *result = out_pwstr_from_str_in::<A>(lock_string(value).as_str())? };
+        Ok(())
+    }
+}
+
+pub(crate) fn set_string(value: &Mutex<String>, source: &PCWSTR) -> Result<()> {
+    *lock_string(value) = string_from_pcwstr(source);
+    Ok(())
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub(crate) fn get_bool(value: &AtomicBool, result: *mut BOOL) -> Result<()> {
+    if result.is_null() {
+        E_POINTER.ok()
+    } else {
+        unsafe { *result = value.load(Ordering::Acquire).into() };
+        Ok(())
+    }
+}
+
+pub(crate) fn set_bool(value: &AtomicBool, source: BOOL) -> Result<()> {
+    value.store(source.into(), Ordering::Release);
+    Ok(())
+}
+
+#[allow(non_snake_case)]
 impl ICoreWebView2EnvironmentOptions_Impl for CoreWebView2EnvironmentOptions {
     fn AdditionalBrowserArguments(&self, result: *mut PWSTR) -> Result<()> {
-        if result.is_null() {
-            E_POINTER.ok()
-        } else {
-            unsafe {
-                *result = pwstr_from_str((*self.additional_browser_arguments.get()).as_str())
-            };
-            Ok(())
-        }
+        get_string(&self.additional_browser_arguments, result)
     }
 
     fn SetAdditionalBrowserArguments(&self, value: &PCWSTR) -> Result<()> {
-        unsafe { *self.additional_browser_arguments.get() = string_from_pcwstr(value) };
-        Ok(())
+        set_string(&self.additional_browser_arguments, value)
     }
 
     fn Language(&self, result: *mut PWSTR) -> Result<()> {
-        if result.is_null() {
-            E_POINTER.ok()
-        } else {
-            unsafe { *result = pwstr_from_str((*self.language.get()).as_str()) };
-            Ok(())
-        }
+        get_string(&self.language, result)
     }
 
     fn SetLanguage(&self, value: &PCWSTR) -> Result<()> {
-        unsafe { *self.language.get() = string_from_pcwstr(value) };
-        Ok(())
+        set_string(&self.language, value)
     }
 
     fn TargetCompatibleBrowserVersion(&self, result: *mut PWSTR) -> Result<()> {
-        if result.is_null() {
-            E_POINTER.ok()
-        } else {
-            unsafe {
-                *result = pwstr_from_str((*self.target_compatible_browser_version.get()).as_str())
-            };
-            Ok(())
-        }
+        get_string(&self.target_compatible_browser_version, result)
     }
 
     fn SetTargetCompatibleBrowserVersion(&self, value: &PCWSTR) -> Result<()> {
-        unsafe { *self.target_compatible_browser_version.get() = string_from_pcwstr(value) };
-        Ok(())
+        set_string(&self.target_compatible_browser_version, value)
     }
 
     fn AllowSingleSignOnUsingOSPrimaryAccount(&self, result: *mut BOOL) -> Result<()> {
-        if result.is_null() {
-            E_POINTER.ok()
-        } else {
-            unsafe { *result = (*self.allow_single_sign_on_using_os_primary_account.get()).into() };
-            Ok(())
-        }
+        get_bool(&self.allow_single_sign_on_using_os_primary_account, result)
     }
 
     fn SetAllowSingleSignOnUsingOSPrimaryAccount(&self, value: BOOL) -> Result<()> {
-        unsafe {
-            *self.allow_single_sign_on_using_os_primary_account.get() = value.into();
-        }
-        Ok(())
+        set_bool(&self.allow_single_sign_on_using_os_primary_account, value)
+    }
+}
+
+#[allow(non_snake_case)]
+impl ICoreWebView2EnvironmentOptions2_Impl for CoreWebView2EnvironmentOptions {
+    fn ExclusiveUserDataFolderAccess(&self, result: *mut BOOL) -> Result<()> {
+        get_bool(&self.exclusive_user_data_folder_access, result)
+    }
+
+    fn SetExclusiveUserDataFolderAccess(&self, value: BOOL) -> Result<()> {
+        set_bool(&self.exclusive_user_data_folder_access, value)
+    }
+}
+
+/// Typed builder for [`CoreWebView2EnvironmentOptions`], along with the browser executable folder
+/// and user data folder which are passed to `CreateCoreWebView2EnvironmentWithOptions` next to
+/// the options. Anything which is not set keeps the same default as
+/// [`CoreWebView2EnvironmentOptions::default`].
+///
+/// With the `serde` feature, the builder can also be deserialized from a config file, using the
+/// snake_case names of the setters as keys.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct EnvironmentOptionsBuilder {
+    additional_browser_arguments: Option<String>,
+    language: Option<String>,
+    target_compatible_browser_version: Option<String>,
+    allow_single_sign_on_using_os_primary_account: Option<bool>,
+    exclusive_user_data_folder_access: Option<bool>,
+    browser_executable_folder: Option<PathBuf>,
+    user_data_folder: Option<PathBuf>,
+}
+
+impl EnvironmentOptionsBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Extra command line switches for the browser process, e.g. `--disable-gpu`.
+    pub fn additional_browser_arguments<T: Into<String>>(mut self, value: T) -> Self {
+        self.additional_browser_arguments = Some(value.into());
+        self
+    }
+
+    /// Same as [`EnvironmentOptionsBuilder::additional_browser_arguments`], but serialized from
+    /// [`BrowserArguments`].
+    pub fn browser_arguments(self, value: &BrowserArguments) -> Self {
+        self.additional_browser_arguments(value.to_string())
+    }
+
+    /// Default display language for WebView2, e.g. `en-US`.
+    pub fn language<T: Into<String>>(mut self, value: T) -> Self {
+        self.language = Some(value.into());
+        self
+    }
+
+    /// Minimum version of the WebView2 Runtime which the app is compatible with. The default is
+    /// the version of the SDK which the bindings were generated from.
+    pub fn target_compatible_browser_version<T: Into<String>>(mut self, value: T) -> Self {
+        self.target_compatible_browser_version = Some(value.into());
+        self
+    }
+
+    pub fn allow_single_sign_on_using_os_primary_account(mut self, value: bool) -> Self {
+        self.allow_single_sign_on_using_os_primary_account = Some(value);
+        self
+    }
+
+    pub fn exclusive_user_data_folder_access(mut self, value: bool) -> Self {
+        self.exclusive_user_data_folder_access = Some(value);
+        self
+    }
+
+    /// Load a fixed version of the WebView2 Runtime from this folder instead of the installed one.
+    pub fn browser_executable_folder<T: Into<PathBuf>>(mut self, value: T) -> Self {
+        self.browser_executable_folder = Some(value.into());
+        self
+    }
+
+    /// Store the browser data in this folder instead of next to the executable.
+    pub fn user_data_folder<T: Into<PathBuf>>(mut self, value: T) -> Self {
+        self.user_data_folder = Some(value.into());
+        self
+    }
+
+    /// Create the COM object for the options. The folders are not part of the options, so they
+    /// are only used by [`EnvironmentOptionsBuilder::create_environment`].
+    pub fn build(&self) -> ICoreWebView2EnvironmentOptions {
+        let defaults = EnvironmentOptionsSnapshot::default();
+        CoreWebView2EnvironmentOptions::from(EnvironmentOptionsSnapshot {
+            additional_browser_arguments: self
+                .additional_browser_arguments
+                .clone()
+                .unwrap_or(defaults.additional_browser_arguments),
+            language: self.language.clone().unwrap_or(defaults.language),
+            target_compatible_browser_version: self
+                .target_compatible_browser_version
+                .clone()
+                .unwrap_or(defaults.target_compatible_browser_version),
+            allow_single_sign_on_using_os_primary_account: self
+                .allow_single_sign_on_using_os_primary_account
+                .unwrap_or(defaults.allow_single_sign_on_using_os_primary_account),
+            exclusive_user_data_folder_access: self
+                .exclusive_user_data_folder_access
+                .unwrap_or(defaults.exclusive_user_data_folder_access),
+        })
+        .into()
+    }
+
+    /// Call `CreateCoreWebView2EnvironmentWithOptions` with the folders and the options from
+    /// [`EnvironmentOptionsBuilder::build`], and pump messages until the environment is ready.
+    pub fn create_environment(&self) -> crate::Result<ICoreWebView2Environment> {
+        let browser_executable_folder = self.browser_executable_folder.as_deref().map(wide_path);
+        let user_data_folder = self.user_data_folder.as_deref().map(wide_path);
+        let options = self.build();
+        let (tx, rx) = mpsc::channel();
+
+        CreateCoreWebView2EnvironmentCompletedHandler::wait_for_async_operation(
+            Box::new(move |handler| unsafe {
+                CreateCoreWebView2EnvironmentWithOptions(
+                    browser_executable_folder
+                        .as_ref()
+                        .map_or_else(PCWSTR::null, PCWSTR::from),
+                    user_data_folder
+                        .as_ref()
+                        .map_or_else(PCWSTR::null, PCWSTR::from),
+                    &options,
+                    &handler,
+                )
+                .map_err(crate::Error::from)
+            }),
+            Box::new(move |error_code, environment| {
+                error_code?;
+                // If the wait was canceled, nobody is left to receive a late result.
+                let _ = tx.send(environment.ok_or_else(|| windows::core::Error::from(E_POINTER)));
+                Ok(())
+            }),
+        )
+        .map_err(crate::Error::environment_creation_error)?;
+
+        rx.recv()
+            .map_err(|_| crate::Error::SendError)?
+            .map_err(crate::Error::from)
     }
 }
 
+#[cfg(windows)]
+fn wide_path(path: &Path) -> HSTRING {
+    path.as_os_str().into()
+}
+
+#[cfg(not(windows))]
+fn wide_path(path: &Path) -> HSTRING {
+    path.to_string_lossy().as_ref().into()
+}
+
 #[cfg(test)]
 mod test {
-    use std::ptr;
+    use std::{ptr, thread};
+
+    use windows::{core::Interface, w};
 
-    use windows::w;
+    use webview2_com_util::{take_pwstr_in, CountingAllocator};
 
     use crate::{
         pwstr::take_pwstr,
         Microsoft::Web::WebView2::Win32::{
-            ICoreWebView2EnvironmentOptions, CORE_WEBVIEW_TARGET_PRODUCT_VERSION,
+            ICoreWebView2EnvironmentOptions, ICoreWebView2EnvironmentOptions2,
+            CORE_WEBVIEW_TARGET_PRODUCT_VERSION,
         },
     };
 
@@ -128,6 +380,47 @@ mod test {
         assert_eq!(&result, "FakeArguments");
     }
 
+    #[test]
+    fn getters_free_once() {
+        CountingAllocator::reset();
+        let value = Mutex::new("FakeLanguage".to_string());
+        let mut result = PWSTR::null();
+        get_string_in::<CountingAllocator>(&value, &mut result).unwrap();
+        assert_eq!(CountingAllocator::counts(), (1, 0));
+        assert_eq!(&take_pwstr_in::<CountingAllocator>(result), "FakeLanguage");
+        assert_eq!(CountingAllocator::counts(), (1, 1));
+
+        let empty = Mutex::new(String::new());
+        let mut result = PWSTR::null();
+        get_string_in::<CountingAllocator>(&empty, &mut result).unwrap();
+        assert!(result.is_null());
+        assert_eq!(&take_pwstr_in::<CountingAllocator>(result), "");
+        assert_eq!(CountingAllocator::counts(), (1, 1));
+
+        let result = get_string_in::<CountingAllocator>(&value, ptr::null_mut());
+        assert_eq!(result.unwrap_err().code(), E_POINTER);
+        assert_eq!(CountingAllocator::counts(), (1, 1));
+    }
+
+    #[test]
+    fn override_browser_arguments() {
+        let mut arguments = BrowserArguments::new();
+        arguments.enable_feature("FakeFeature");
+        arguments.insert("proxy-server", Some("fake proxy"));
+        let options = CoreWebView2EnvironmentOptions::default();
+        options.set_browser_arguments(&arguments);
+        assert_eq!(options.browser_arguments().unwrap(), arguments);
+
+        let options: ICoreWebView2EnvironmentOptions = options.into();
+        let mut result = PWSTR(ptr::null_mut());
+        unsafe { options.AdditionalBrowserArguments(&mut result) }.unwrap();
+        let result = take_pwstr(result);
+        assert_eq!(
+            &result,
+            r#"--enable-features=FakeFeature --proxy-server="fake proxy""#
+        );
+    }
+
     #[test]
     fn override_language() {
         let options: ICoreWebView2EnvironmentOptions =
@@ -179,4 +472,134 @@ mod test {
         unsafe { options.AllowSingleSignOnUsingOSPrimaryAccount(&mut result) }.unwrap();
         assert_eq!(result.0, 1);
     }
+
+    #[test]
+    fn default_exclusive_data_folder() {
+        let options: ICoreWebView2EnvironmentOptions =
+            CoreWebView2EnvironmentOptions::default().into();
+        let options: ICoreWebView2EnvironmentOptions2 = options.cast().unwrap();
+        let mut result = BOOL(1);
+        unsafe { options.ExclusiveUserDataFolderAccess(&mut result) }.unwrap();
+        assert_eq!(result.0, 0);
+    }
+
+    #[test]
+    fn override_exclusive_data_folder() {
+        let options: ICoreWebView2EnvironmentOptions =
+            CoreWebView2EnvironmentOptions::default().into();
+        let options: ICoreWebView2EnvironmentOptions2 = options.cast().unwrap();
+        unsafe { options.SetExclusiveUserDataFolderAccess(BOOL(1)) }.unwrap();
+        let mut result = BOOL(0);
+        unsafe { options.ExclusiveUserDataFolderAccess(&mut result) }.unwrap();
+        assert_eq!(result.0, 1);
+    }
+
+    #[test]
+    fn builder_defaults() {
+        let options = EnvironmentOptionsBuilder::new().build();
+        let mut result = PWSTR(ptr::null_mut::<u16>());
+        unsafe { options.TargetCompatibleBrowserVersion(&mut result) }.unwrap();
+        let result = take_pwstr(result);
+        assert_eq!(&result, CORE_WEBVIEW_TARGET_PRODUCT_VERSION);
+        let mut result = BOOL(1);
+        unsafe { options.AllowSingleSignOnUsingOSPrimaryAccount(&mut result) }.unwrap();
+        assert_eq!(result.0, 0);
+    }
+
+    #[test]
+    fn builder_overrides() {
+        let options = EnvironmentOptionsBuilder::new()
+            .additional_browser_arguments("FakeArguments")
+            .language("FakeLanguage")
+            .target_compatible_browser_version("FakeVersion")
+            .allow_single_sign_on_using_os_primary_account(true)
+            .exclusive_user_data_folder_access(true)
+            .build();
+
+        let mut result = PWSTR(ptr::null_mut::<u16>());
+        unsafe { options.AdditionalBrowserArguments(&mut result) }.unwrap();
+        assert_eq!(&take_pwstr(result), "FakeArguments");
+        let mut result = PWSTR(ptr::null_mut::<u16>());
+        unsafe { options.Language(&mut result) }.unwrap();
+        assert_eq!(&take_pwstr(result), "FakeLanguage");
+        let mut result = PWSTR(ptr::null_mut::<u16>());
+        unsafe { options.TargetCompatibleBrowserVersion(&mut result) }.unwrap();
+        assert_eq!(&take_pwstr(result), "FakeVersion");
+        let mut result = BOOL(0);
+        unsafe { options.AllowSingleSignOnUsingOSPrimaryAccount(&mut result) }.unwrap();
+        assert_eq!(result.0, 1);
+        let options: ICoreWebView2EnvironmentOptions2 = options.cast().unwrap();
+        let mut result = BOOL(0);
+        unsafe { options.ExclusiveUserDataFolderAccess(&mut result) }.unwrap();
+        assert_eq!(result.0, 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_builder() {
+        let builder: EnvironmentOptionsBuilder = serde_json::from_str(
+            r#"{
+                "language": "FakeLanguage",
+                "exclusive_user_data_folder_access": true,
+                "user_data_folder": "C:\\FakeData"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            builder,
+            EnvironmentOptionsBuilder::new()
+                .language("FakeLanguage")
+                .exclusive_user_data_folder_access(true)
+                .user_data_folder("C:\\FakeData")
+        );
+        assert!(
+            serde_json::from_str::<EnvironmentOptionsBuilder>(r#"{ "langauge": "" }"#).is_err()
+        );
+    }
+
+    #[test]
+    fn snapshot_values() {
+        let options = CoreWebView2EnvironmentOptions::default();
+        assert_eq!(options.snapshot(), EnvironmentOptionsSnapshot::default());
+
+        let snapshot = EnvironmentOptionsSnapshot {
+            language: "FakeLanguage".to_string(),
+            allow_single_sign_on_using_os_primary_account: true,
+            exclusive_user_data_folder_access: true,
+            ..Default::default()
+        };
+        let options = CoreWebView2EnvironmentOptions::from(snapshot.clone());
+        assert_eq!(options.snapshot(), snapshot);
+
+        options.set_browser_arguments(&"--disable-gpu".parse().unwrap());
+        assert_eq!(
+            options.snapshot(),
+            EnvironmentOptionsSnapshot {
+                additional_browser_arguments: "--disable-gpu".to_string(),
+                ..snapshot
+            }
+        );
+    }
+
+    #[test]
+    fn prepare_on_worker_thread() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<CoreWebView2EnvironmentOptions>();
+
+        let options = thread::spawn(|| {
+            let options = CoreWebView2EnvironmentOptions::from(EnvironmentOptionsSnapshot {
+                language: "FakeLanguage".to_string(),
+                ..Default::default()
+            });
+            options.set_browser_arguments(&"--disable-gpu".parse().unwrap());
+            options
+        })
+        .join()
+        .unwrap();
+
+        let options: ICoreWebView2EnvironmentOptions = options.into();
+        let mut result = PWSTR(ptr::null_mut::<u16>());
+        unsafe { options.AdditionalBrowserArguments(&mut result) }.unwrap();
+        assert_eq!(&take_pwstr(result), "--disable-gpu");
+    }
 }
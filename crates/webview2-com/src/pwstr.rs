@@ -1,144 +1,116 @@
-use std::{fmt::Display, marker::PhantomData, mem, ptr};
+use std::ffi::c_void;
 
 use windows::{
-    core::{PCWSTR, PWSTR},
-    Win32::{Globalization::lstrlenW, System::Com},
+    core::PWSTR,
+    Win32::{Foundation::E_OUTOFMEMORY, System::Com},
 };
 
-/// RAII holder for a [`PWSTR`] which is allocated with [`Com::CoTaskMemAlloc`] and freed
-/// with [`Com::CoTaskMemFree`] when dropped.
-pub struct CoTaskMemPWSTR<'a>(PWSTR, PhantomData<&'a PWSTR>);
+use webview2_com_util::{
+    pwstr_from_str_in, take_pwstr_in, try_pwstr_from_str_in, try_take_pwstr_in, ComAllocator,
+    ComString,
+};
+
+use crate::Result;
 
-/// Constant guard object tied to the lifetime of the [`CoTaskMemPWSTR`] so that it
-/// is safe to dereference the [`PCWSTR`] as long as both are still in scope.
-pub struct CoTaskMemRef<'a>(PCWSTR, PhantomData<&'a PCWSTR>);
+/// The allocator which WebView2 expects for every string out-param: [`Com::CoTaskMemAlloc`] and
+/// [`Com::CoTaskMemFree`].
+pub struct CoTaskMemAllocator;
 
-impl<'a> CoTaskMemRef<'a> {
-    pub fn as_pcwstr(&self) -> &PCWSTR {
-        &self.0
+unsafe impl ComAllocator for CoTaskMemAllocator {
+    fn alloc(size: usize) -> *mut c_void {
+        unsafe { Com::CoTaskMemAlloc(size) }
     }
-}
 
-impl<'a> From<&'a CoTaskMemPWSTR<'a>> for CoTaskMemRef<'a> {
-    fn from(value: &'a CoTaskMemPWSTR<'a>) -> Self {
-        Self(PCWSTR(value.0 .0), PhantomData)
+    unsafe fn free(buffer: *mut c_void) {
+        Com::CoTaskMemFree(buffer as *const _);
     }
 }
 
-/// Mutable guard object tied to the lifetime of the [`CoTaskMemPWSTR`] so that it
-/// is safe to dereference the [`PWSTR`] as long as both are still in scope.
-pub struct CoTaskMemMut<'a>(&'a PWSTR);
+/// [`ComString`] which is allocated with [`Com::CoTaskMemAlloc`] and freed with
+/// [`Com::CoTaskMemFree`].
+pub type CoTaskMemPWSTR<'a> = ComString<'a, CoTaskMemAllocator>;
 
-impl<'a> CoTaskMemMut<'a> {
-    pub fn as_pwstr(&mut self) -> &'a PWSTR {
-        self.0
-    }
+/// Copy a [`PWSTR`] allocated with [`Com::CoTaskMemAlloc`] from an input param to a [`String`]
+/// and free the original buffer with [`Com::CoTaskMemFree`].
+pub fn take_pwstr(source: PWSTR) -> String {
+    take_pwstr_in::<CoTaskMemAllocator>(source)
 }
 
-impl<'a> From<&'a mut CoTaskMemPWSTR<'a>> for CoTaskMemMut<'a> {
-    fn from(value: &'a mut CoTaskMemPWSTR<'a>) -> Self {
-        Self(&value.0)
-    }
+/// Copy a [`PWSTR`] allocated with [`Com::CoTaskMemAlloc`] from an input param to a [`String`]
+/// and free the original buffer with [`Com::CoTaskMemFree`], or return an error if it is not
+/// valid UTF-16. The buffer is freed either way.
+pub fn try_take_pwstr(source: PWSTR) -> Result<String> {
+    Ok(try_take_pwstr_in::<CoTaskMemAllocator>(source)?)
 }
 
-impl<'a> CoTaskMemPWSTR<'a> {
-    /// Get a mutable [`PWSTR`] guard which borrows the pointer.
-    pub fn as_mut(&'a mut self) -> CoTaskMemMut<'a> {
-        From::from(self)
-    }
-
-    /// Get a constant [`PCWSTR`] guard which borrows the pointer.
-    pub fn as_ref(&'a self) -> CoTaskMemRef<'a> {
-        From::from(self)
-    }
-
-    /// Take the [`PWSTR`] pointer and hand off ownership so that it is not freed when the `CoTaskMemPWSTR` is dropped.
-    pub fn take(&mut self) -> PWSTR {
-        let result = self.0;
-        self.0 .0 = ptr::null_mut();
-        result
-    }
+/// Allocate a [`PWSTR`] with [`Com::CoTaskMemAlloc`] and copy a [`&str`] into it.
+pub fn pwstr_from_str(source: &str) -> PWSTR {
+    pwstr_from_str_in::<CoTaskMemAllocator>(source)
 }
 
-impl<'a> Drop for CoTaskMemPWSTR<'a> {
-    fn drop(&mut self) {
-        if !self.0 .0.is_null() {
-            unsafe {
-                Com::CoTaskMemFree(mem::transmute(self.0 .0));
-            }
-            self.0 .0 = ptr::null_mut();
-        }
-    }
+/// Allocate a [`PWSTR`] with [`Com::CoTaskMemAlloc`] and copy a [`&str`] into it, using the
+/// strict rules from [`ComString::try_from_str`].
+pub fn try_pwstr_from_str(source: &str) -> Result<PWSTR> {
+    Ok(try_pwstr_from_str_in::<CoTaskMemAllocator>(source)?)
 }
 
-impl<'a> Default for CoTaskMemPWSTR<'a> {
-    fn default() -> Self {
-        Self(PWSTR(ptr::null_mut()), PhantomData)
-    }
+/// Like [`pwstr_from_str`], for the string out-params of the COM interfaces in this crate. It
+/// returns `E_OUTOFMEMORY` to the caller instead of aborting if the allocation fails.
+pub(crate) fn out_pwstr_from_str(source: &str) -> windows::core::Result<PWSTR> {
+    out_pwstr_from_str_in::<CoTaskMemAllocator>(source)
 }
 
-impl<'a> From<PWSTR> for CoTaskMemPWSTR<'a> {
-    fn from(value: PWSTR) -> Self {
-        Self(value, PhantomData)
-    }
+/// Like [`out_pwstr_from_str`], but allocate the [`PWSTR`] with `A`.
+pub(crate) fn out_pwstr_from_str_in<A: ComAllocator>(source: &str) -> windows::core::Result<PWSTR> {
+    ComString::<A>::new(source)
+        .map(|mut value| value.take())
+        .map_err(|_| E_OUTOFMEMORY.into())
 }
 
-impl<'a> From<&str> for CoTaskMemPWSTR<'a> {
-    fn from(value: &str) -> Self {
-        match value {
-            "" => Default::default(),
-            value => {
-                let encoded: Vec<_> = value.encode_utf16().chain(std::iter::once(0)).collect();
-
-                unsafe {
-                    let mut buffer =
-                        Com::CoTaskMemAlloc(encoded.len() * mem::size_of::<u16>()) as *mut u16;
-                    let result = PWSTR(buffer);
-
-                    for char in encoded {
-                        *buffer = char;
-                        buffer = buffer.add(1);
-                    }
-
-                    Self(result, PhantomData)
-                }
-            }
-        }
-    }
-}
+#[cfg(test)]
+mod test {
+    use webview2_com_util::{string_from_pcwstr, CountingAllocator};
 
-impl<'a> Display for CoTaskMemPWSTR<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let value = string_from_pcwstr(self.as_ref().as_pcwstr());
-        f.write_str(value.as_str())
+    use super::*;
+
+    #[test]
+    fn out_param_freed_once() {
+        CountingAllocator::reset();
+        let pwstr = out_pwstr_from_str_in::<CountingAllocator>("Fake Value").unwrap();
+        assert_eq!(CountingAllocator::counts(), (1, 0));
+        assert_eq!(&take_pwstr_in::<CountingAllocator>(pwstr), "Fake Value");
+        assert_eq!(CountingAllocator::counts(), (1, 1));
+
+        assert!(out_pwstr_from_str_in::<CountingAllocator>("")
+            .unwrap()
+            .is_null());
+        assert_eq!(CountingAllocator::counts(), (1, 1));
     }
-}
 
-/// Copy a [`PCWSTR`] from an input param to a [`String`].
-pub fn string_from_pcwstr(source: &PCWSTR) -> String {
-    if source.0.is_null() {
-        String::new()
-    } else {
-        let len = unsafe { lstrlenW(*source) };
-
-        if len > 0 {
-            unsafe {
-                let buffer = ptr::slice_from_raw_parts(source.0, len as usize);
-                String::from_utf16_lossy(&*buffer)
-            }
-        } else {
-            String::new()
-        }
+    #[test]
+    fn co_task_mem_round_trip() {
+        let pwstr = pwstr_from_str("Fake Value");
+        assert_eq!(&take_pwstr(pwstr), "Fake Value");
+        let pwstr = out_pwstr_from_str("Fake Value").unwrap();
+        assert_eq!(&try_take_pwstr(pwstr).unwrap(), "Fake Value");
     }
-}
 
-/// Copy a [`PWSTR`] allocated with [`Com::CoTaskMemAlloc`] from an input param to a [`String`]
-/// and free the original buffer with [`Com::CoTaskMemFree`].
-pub fn take_pwstr(source: PWSTR) -> String {
-    CoTaskMemPWSTR::from(source).to_string()
-}
+    #[test]
+    fn empty_strings() {
+        assert!(pwstr_from_str("").is_null());
+        assert_eq!(&take_pwstr(PWSTR::null()), "");
+        assert!(out_pwstr_from_str("").unwrap().is_null());
+    }
 
-/// Allocate a [`PWSTR`] with [`Com::CoTaskMemAlloc`] and copy a [`&str`] into it.
-pub fn pwstr_from_str(source: &str) -> PWSTR {
-    CoTaskMemPWSTR::from(source).take()
+    #[test]
+    fn strict_round_trip() {
+        let value = "Fake \u{1F600} Value";
+        let pwstr = try_pwstr_from_str(value).unwrap();
+        assert_eq!(&string_from_pcwstr(&windows::core::PCWSTR(pwstr.0)), value);
+        assert_eq!(&try_take_pwstr(pwstr).unwrap(), value);
+        assert!(matches!(
+            try_pwstr_from_str("Fake\0Value"),
+            Err(crate::Error::InvalidString(_))
+        ));
+    }
 }
@@ -0,0 +1,96 @@
+use windows::Win32::System::WinRT::EventRegistrationToken;
+
+/// Closure which passes an [`EventRegistrationToken`] to the matching `remove_*` method.
+pub type RemoveEventClosure = Box<dyn FnOnce(EventRegistrationToken) -> windows::core::Result<()>>;
+
+/// RAII guard for an event handler which was registered with one of the `add_*` methods. When
+/// the guard is dropped, it calls the matching `remove_*` method with the
+/// [`EventRegistrationToken`], which releases the handler and anything its closure captured.
+pub struct EventRegistration {
+    token: EventRegistrationToken,
+    remove: Option<RemoveEventClosure>,
+}
+
+impl EventRegistration {
+    /// Take ownership of a `token` returned by an `add_*` method, along with a closure that
+    /// passes it to the matching `remove_*` method.
+    pub fn new(token: EventRegistrationToken, remove: RemoveEventClosure) -> Self {
+        Self {
+            token,
+            remove: Some(remove),
+        }
+    }
+
+    /// Get the [`EventRegistrationToken`] without giving up ownership.
+    pub fn token(&self) -> EventRegistrationToken {
+        self.token
+    }
+
+    /// Unregister the handler now instead of waiting for the guard to be dropped, and report any
+    /// error from the `remove_*` method which would otherwise be ignored.
+    pub fn unregister(mut self) -> crate::Result<()> {
+        match self.remove.take() {
+            Some(remove) => remove(self.token).map_err(crate::Error::from),
+            None => Ok(()),
+        }
+    }
+
+    /// Hand off ownership of the [`EventRegistrationToken`] so that the handler stays registered
+    /// when the guard is dropped.
+    pub fn into_token(mut self) -> EventRegistrationToken {
+        self.remove = None;
+        self.token
+    }
+}
+
+impl Drop for EventRegistration {
+    fn drop(&mut self) {
+        if let Some(remove) = self.remove.take() {
+            let _ = remove(self.token);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    fn track_removed() -> (Rc<RefCell<Vec<i64>>>, RemoveEventClosure) {
+        let removed = Rc::new(RefCell::new(Vec::new()));
+        let tracker = removed.clone();
+        (
+            removed,
+            Box::new(move |token| {
+                tracker.borrow_mut().push(token.value);
+                Ok(())
+            }),
+        )
+    }
+
+    #[test]
+    fn remove_on_drop() {
+        let (removed, remove) = track_removed();
+        let registration = EventRegistration::new(EventRegistrationToken { value: 1 }, remove);
+        assert!(removed.borrow().is_empty());
+        drop(registration);
+        assert_eq!(*removed.borrow(), vec![1]);
+    }
+
+    #[test]
+    fn unregister_once() {
+        let (removed, remove) = track_removed();
+        let registration = EventRegistration::new(EventRegistrationToken { value: 2 }, remove);
+        registration.unregister().unwrap();
+        assert_eq!(*removed.borrow(), vec![2]);
+    }
+
+    #[test]
+    fn into_token_keeps_handler() {
+        let (removed, remove) = track_removed();
+        let registration = EventRegistration::new(EventRegistrationToken { value: 3 }, remove);
+        assert_eq!(registration.into_token().value, 3);
+        assert!(removed.borrow().is_empty());
+    }
+}
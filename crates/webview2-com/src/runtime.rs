@@ -0,0 +1,382 @@
+use std::{
+    env,
+    ffi::OsString,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{BrowserChannel, BrowserVersion, Error, Result};
+
+/// Name of the executable which marks the root of a WebView2 Runtime folder.
+pub const BROWSER_EXECUTABLE: &str = "msedgewebview2.exe";
+
+/// Environment variable which the WebView2 loader also checks for a fixed-version runtime.
+pub const BROWSER_EXECUTABLE_FOLDER_VAR: &str = "WEBVIEW2_BROWSER_EXECUTABLE_FOLDER";
+
+/// A fixed-version WebView2 Runtime which was found by [`RuntimeLocator::locate`]. Pass
+/// `folder` as the `browserExecutableFolder` to `CreateCoreWebView2EnvironmentWithOptions`, e.g.
+/// with [`crate::EnvironmentOptionsBuilder::browser_executable_folder`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FixedVersionRuntime {
+    pub folder: PathBuf,
+    pub version: BrowserVersion,
+}
+
+/// Searches a list of directories for a fixed-version WebView2 Runtime.
+///
+/// Each directory may be a runtime folder itself, i.e. it contains `msedgewebview2.exe`, or it
+/// may hold one or more runtime folders, e.g.
+/// `Microsoft.WebView2.FixedVersionRuntime.104.0.1293.44.x64`. The version is read from the
+/// version folder next to `msedgewebview2.exe`, e.g. `104.0.1293.44`, and only if there is none,
+/// it falls back to the first version in the name of the runtime folder. The first directory
+/// which has a runtime at or above the minimum version wins, and within a directory the newest
+/// runtime wins.
+#[derive(Clone, Debug, Default)]
+pub struct RuntimeLocator {
+    search_paths: Vec<PathBuf>,
+    minimum_version: Option<BrowserVersion>,
+}
+
+impl RuntimeLocator {
+    /// Create a locator without any search paths.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a locator which searches the folder named by `WEBVIEW2_BROWSER_EXECUTABLE_FOLDER`,
+    /// the folder containing the current executable, and the `WebView2` folder next to it.
+    pub fn with_default_paths() -> Self {
+        let locator = Self::new().search_env_var(BROWSER_EXECUTABLE_FOLDER_VAR);
+
+        match env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        {
+            Some(exe_folder) => locator
+                .search_path(&exe_folder)
+                .search_path(exe_folder.join("WebView2")),
+            None => locator,
+        }
+    }
+
+    /// Add a directory to the end of the search list.
+    pub fn search_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.search_paths.push(path.into());
+        self
+    }
+
+    /// Add the directory named by an environment variable to the end of the search list, if the
+    /// variable is set when this is called.
+    pub fn search_env_var(self, name: &str) -> Self {
+        self.search_env_var_with(name, |name| env::var_os(name))
+    }
+
+    fn search_env_var_with<F>(self, name: &str, lookup: F) -> Self
+    where
+        F: FnOnce(&str) -> Option<OsString>,
+    {
+        match lookup(name) {
+            Some(path) if !path.is_empty() => self.search_path(path),
+            _ => self,
+        }
+    }
+
+    /// Skip any runtime which is older than `version`.
+    pub fn minimum_version(mut self, version: BrowserVersion) -> Self {
+        self.minimum_version = Some(version);
+        self
+    }
+
+    /// Search the directories in order, and return the first runtime which is new enough. If
+    /// none is found, the error lists what was found in each directory.
+    pub fn locate(&self) -> Result<FixedVersionRuntime> {
+        let mut rejected = Vec::new();
+
+        for path in &self.search_paths {
+            let newest = match scan_path(path)
+                .into_iter()
+                .max_by_key(|runtime| runtime.version)
+            {
+                Some(newest) => newest,
+                None => {
+                    rejected.push(Rejected::NotFound(path.clone()));
+                    continue;
+                }
+            };
+
+            match self.minimum_version {
+                Some(minimum) if newest.version < minimum => {
+                    rejected.push(Rejected::TooOld(newest, minimum))
+                }
+                _ => return Ok(newest),
+            }
+        }
+
+        let message = if rejected.is_empty() {
+            "no search paths for the WebView2 Runtime".to_string()
+        } else {
+            rejected
+                .iter()
+                .map(Rejected::to_string)
+                .collect::<Vec<_>>()
+                .join("; ")
+        };
+        Err(Error::RuntimeNotFound(message))
+    }
+}
+
+enum Rejected {
+    NotFound(PathBuf),
+    TooOld(FixedVersionRuntime, BrowserVersion),
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rejected::NotFound(path) => {
+                write!(f, "{}: no fixed-version runtime found", path.display())
+            }
+            Rejected::TooOld(runtime, minimum) => write!(
+                f,
+                "{}: version {} is older than {}",
+                runtime.folder.display(),
+                runtime.version,
+                minimum
+            ),
+        }
+    }
+}
+
+/// Find every runtime folder in `path`, either `path` itself or its immediate subfolders.
+fn scan_path(path: &Path) -> Vec<FixedVersionRuntime> {
+    if let Some(runtime) = check_runtime_folder(path) {
+        return vec![runtime];
+    }
+
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| check_runtime_folder(&entry.path()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn check_runtime_folder(path: &Path) -> Option<FixedVersionRuntime> {
+    if !path.join(BROWSER_EXECUTABLE).is_file() {
+        return None;
+    }
+
+    let version = version_from_layout(path).or_else(|| {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(version_from_folder_name)
+    })?;
+
+    Some(FixedVersionRuntime {
+        folder: path.to_path_buf(),
+        version,
+    })
+}
+
+/// Find the newest version folder inside of a runtime folder, e.g. `104.0.1293.44`.
+fn version_from_layout(path: &Path) -> Option<BrowserVersion> {
+    fs::read_dir(path)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|name| BrowserVersion::parse(name).ok())
+        })
+        .max()
+}
+
+/// Find the first run of 4 numbers separated by `.` in a folder name, e.g. the version in
+/// `Microsoft.WebView2.FixedVersionRuntime.104.0.1293.44.x64` or just `104.0.1293.44`.
+fn version_from_folder_name(name: &str) -> Option<BrowserVersion> {
+    let parts: Vec<_> = name.split('.').collect();
+
+    parts.windows(4).find_map(|window| {
+        let mut numbers = [0; 4];
+        for (number, part) in numbers.iter_mut().zip(window) {
+            *number = part.parse().ok()?;
+        }
+        let [major, minor, build, patch] = numbers;
+        Some(BrowserVersion::new(
+            major,
+            minor,
+            build,
+            patch,
+            BrowserChannel::Stable,
+        ))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        process,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    /// Temporary directory which is removed when it is dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+            let path = env::temp_dir().join(format!(
+                "webview2-com-runtime-{}-{}",
+                process::id(),
+                NEXT_ID.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn add_runtime(&self, name: &str) -> PathBuf {
+            let folder = self.0.join(name);
+            fs::create_dir_all(&folder).unwrap();
+            fs::write(folder.join(BROWSER_EXECUTABLE), b"").unwrap();
+            folder
+        }
+
+        fn add_layout(&self, name: &str, version: &str) -> PathBuf {
+            let folder = self.add_runtime(name);
+            fs::create_dir_all(folder.join(version)).unwrap();
+            folder
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn version(version: &str) -> BrowserVersion {
+        BrowserVersion::parse(version).unwrap()
+    }
+
+    #[test]
+    fn folder_names() {
+        assert_eq!(
+            version_from_folder_name("Microsoft.WebView2.FixedVersionRuntime.104.0.1293.44.x64"),
+            Some(version("104.0.1293.44"))
+        );
+        assert_eq!(
+            version_from_folder_name("104.0.1293.44"),
+            Some(version("104.0.1293.44"))
+        );
+        assert_eq!(version_from_folder_name("WebView2.104.0"), None);
+    }
+
+    #[test]
+    fn layout_version() {
+        let dir = TempDir::new();
+        let folder = dir.add_layout("WebView2", "104.0.1293.44");
+        fs::write(folder.join("105.0.1343.4"), b"").unwrap();
+
+        let runtime = RuntimeLocator::new().search_path(&folder).locate().unwrap();
+        assert_eq!(runtime.folder, folder);
+        assert_eq!(runtime.version, version("104.0.1293.44"));
+
+        let renamed = dir.add_layout(
+            "Microsoft.WebView2.FixedVersionRuntime.103.0.1264.77.x64",
+            "105.0.1343.10",
+        );
+        let runtime = RuntimeLocator::new()
+            .search_path(&renamed)
+            .locate()
+            .unwrap();
+        assert_eq!(runtime.version, version("105.0.1343.10"));
+
+        let unknown = dir.add_runtime("Unknown");
+        let result = RuntimeLocator::new().search_path(&unknown).locate();
+        assert!(matches!(result, Err(Error::RuntimeNotFound(_))));
+    }
+
+    #[test]
+    fn newest_runtime_in_folder() {
+        let dir = TempDir::new();
+        dir.add_runtime("Microsoft.WebView2.FixedVersionRuntime.103.0.1264.77.x64");
+        let newest = dir.add_runtime("Microsoft.WebView2.FixedVersionRuntime.104.0.1293.44.x64");
+        fs::create_dir_all(dir.0.join("105.0.1343.4")).unwrap();
+
+        let runtime = RuntimeLocator::new().search_path(&dir.0).locate().unwrap();
+        assert_eq!(runtime.folder, newest);
+        assert_eq!(runtime.version, version("104.0.1293.44"));
+    }
+
+    #[test]
+    fn runtime_folder_itself() {
+        let dir = TempDir::new();
+        let folder = dir.add_runtime("104.0.1293.44");
+
+        let runtime = RuntimeLocator::new()
+            .search_path(dir.0.join("missing"))
+            .search_path(&folder)
+            .locate()
+            .unwrap();
+        assert_eq!(runtime.folder, folder);
+    }
+
+    #[test]
+    fn minimum_version() {
+        let old = TempDir::new();
+        old.add_runtime("103.0.1264.77");
+        let new = TempDir::new();
+        let folder = new.add_runtime("104.0.1293.44");
+
+        let runtime = RuntimeLocator::new()
+            .search_path(&old.0)
+            .search_path(&new.0)
+            .minimum_version(version("104.0.1293.0"))
+            .locate()
+            .unwrap();
+        assert_eq!(runtime.folder, folder);
+
+        let result = RuntimeLocator::new()
+            .search_path(&old.0)
+            .minimum_version(version("104.0.1293.0"))
+            .locate();
+        match result {
+            Err(Error::RuntimeNotFound(message)) => {
+                assert!(message.contains("version 103.0.1264.77 is older than 104.0.1293.0"))
+            }
+            _ => panic!("expected RuntimeNotFound"),
+        }
+    }
+
+    #[test]
+    fn search_env_var() {
+        let dir = TempDir::new();
+        let folder = dir.add_runtime("104.0.1293.44");
+        let name = format!("WEBVIEW2_COM_TEST_RUNTIME_FOLDER_{}", process::id());
+        let lookup = |var: &str| (var == name).then(|| folder.clone().into_os_string());
+
+        let runtime = RuntimeLocator::new()
+            .search_env_var_with(&name, lookup)
+            .search_env_var_with("WEBVIEW2_COM_TEST_EMPTY", |_| Some(OsString::new()))
+            .locate()
+            .unwrap();
+        assert_eq!(runtime.folder, folder);
+    }
+
+    #[test]
+    fn not_found() {
+        let dir = TempDir::new();
+        let result = RuntimeLocator::new().search_path(&dir.0).locate();
+        assert!(matches!(result, Err(Error::RuntimeNotFound(_))));
+        assert!(matches!(
+            RuntimeLocator::new().locate(),
+            Err(Error::RuntimeNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    any::Any,
+    cell::RefCell,
+    panic::{self, AssertUnwindSafe},
+    process,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use windows::Win32::Foundation::E_UNEXPECTED;
+
+/// What to do after a closure passed to one of the callback handlers panics. Unwinding across the
+/// COM boundary is undefined behavior, so the generated `Invoke` methods always catch the panic
+/// and return `E_UNEXPECTED` to WebView2 instead, then they apply the current policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PanicPolicy {
+    /// Abort the process. This is the default.
+    Abort,
+    /// Write the panic message to `stderr` and drop the payload.
+    Log,
+    /// Hold onto the payload, so it can be re-raised on the thread which is pumping messages.
+    /// The message loops in this crate call [`resume_callback_panic`] after dispatching each
+    /// message, or you can call [`take_callback_panic`] from your own message loop.
+    Propagate,
+}
+
+static PANIC_POLICY: AtomicU8 = AtomicU8::new(PanicPolicy::Abort as u8);
+
+thread_local! {
+    static CALLBACK_PANIC: RefCell<Option<Box<dyn Any + Send>>> = RefCell::new(None);
+}
+
+/// Set the global [`PanicPolicy`] for all of the callback handlers.
+pub fn set_panic_policy(policy: PanicPolicy) {
+    PANIC_POLICY.store(policy as u8, Ordering::Release);
+}
+
+/// Get the global [`PanicPolicy`] for all of the callback handlers.
+pub fn panic_policy() -> PanicPolicy {
+    match PANIC_POLICY.load(Ordering::Acquire) {
+        policy if policy == PanicPolicy::Log as u8 => PanicPolicy::Log,
+        policy if policy == PanicPolicy::Propagate as u8 => PanicPolicy::Propagate,
+        _ => PanicPolicy::Abort,
+    }
+}
+
+/// Take the payload of the first panic which was caught on this thread with
+/// [`PanicPolicy::Propagate`], if there is one.
+pub fn take_callback_panic() -> Option<Box<dyn Any + Send>> {
+    CALLBACK_PANIC.with(|payload| payload.borrow_mut().take())
+}
+
+/// Re-raise the panic returned by [`take_callback_panic`], if there is one.
+pub fn resume_callback_panic() {
+    if let Some(payload) = take_callback_panic() {
+        panic::resume_unwind(payload);
+    }
+}
+
+/// Call the closure for an `Invoke` method without letting a panic unwind into the caller.
+pub(crate) fn catch_invoke<F>(f: F) -> windows::core::Result<()>
+where
+    F: FnOnce() -> windows::core::Result<()>,
+{
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => {
+            match panic_policy() {
+                PanicPolicy::Abort => process::abort(),
+                PanicPolicy::Log => {
+                    let message = payload
+                        .downcast_ref::<&str>()
+                        .copied()
+                        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                        .unwrap_or("Box<dyn Any>");
+                    eprintln!("webview2-com: callback panicked: {}", message);
+                }
+                PanicPolicy::Propagate => CALLBACK_PANIC.with(|pending| {
+                    pending.borrow_mut().get_or_insert(payload);
+                }),
+            }
+
+            Err(E_UNEXPECTED.into())
+        }
+    }
+}
+
+/// Hold the global [`PanicPolicy`] for the duration of a test, so tests which change it do not
+/// race with each other. Dropping it restores the default and lets the next one proceed.
+#[cfg(test)]
+pub(crate) struct ScopedPanicPolicy(());
+
+#[cfg(test)]
+static SCOPED_POLICY_LOCK: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+#[cfg(test)]
+impl ScopedPanicPolicy {
+    pub(crate) fn new(policy: PanicPolicy) -> Self {
+        while SCOPED_POLICY_LOCK
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            std::thread::yield_now();
+        }
+        set_panic_policy(policy);
+        Self(())
+    }
+
+    pub(crate) fn set(&self, policy: PanicPolicy) {
+        set_panic_policy(policy);
+    }
+}
+
+#[cfg(test)]
+impl Drop for ScopedPanicPolicy {
+    fn drop(&mut self) {
+        set_panic_policy(PanicPolicy::Abort);
+        SCOPED_POLICY_LOCK.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn no_panic() {
+        assert!(catch_invoke(|| Ok(())).is_ok());
+        assert!(take_callback_panic().is_none());
+    }
+}